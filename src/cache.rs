@@ -0,0 +1,203 @@
+//! Bodies of successful GETs, kept so repeated reads skip the network.
+
+use std::collections::{HashMap, VecDeque};
+
+/// Most responses kept at once
+const MAX_ENTRIES: usize = 64;
+
+/// Most body bytes kept at once; a larger body is never cached
+const MAX_BYTES: usize = 4 * 1024 * 1024;
+
+/// Raw response bodies by request path, query included. Once over
+/// `MAX_ENTRIES` or `MAX_BYTES`, the oldest responses are dropped first.
+#[derive(Default)]
+pub(crate) struct ResponseCache {
+    entries: HashMap<String, Vec<u8>>,
+    // Paths in insertion order, oldest first
+    order: VecDeque<String>,
+    bytes: usize,
+}
+
+impl ResponseCache {
+    /// Body cached for `path`, if any
+    pub(crate) fn get(&self, path: &str) -> Option<&[u8]> {
+        self.entries.get(path).map(Vec::as_slice)
+    }
+
+    /// Remember `body` for `path`, evicting the oldest responses over the caps
+    pub(crate) fn insert(&mut self, path: &str, body: Vec<u8>) {
+        if body.len() > MAX_BYTES {
+            return;
+        }
+
+        self.retain(|key| key != path);
+        self.bytes += body.len();
+        self.order.push_back(path.to_string());
+        self.entries.insert(path.to_string(), body);
+
+        while self.entries.len() > MAX_ENTRIES || self.bytes > MAX_BYTES {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some(body) = self.entries.remove(&oldest) {
+                self.bytes -= body.len();
+            }
+        }
+    }
+
+    /// Forget every response
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.bytes = 0;
+    }
+
+    /// Forget the responses of `path`, with or without a query
+    pub(crate) fn invalidate_path(&mut self, path: &str) {
+        self.retain(|key| base(key) != path);
+    }
+
+    /// Forget every response that may hold records of `collection`: those
+    /// under it (`/posts/1`, `/users/1/posts`), embedding or expanding it
+    /// (`/users?_embed=posts`, `/comments/1?_expand=post`) and filtering on
+    /// its ids (`/comments?postId=1`)
+    pub(crate) fn invalidate_resource(&mut self, collection: &str) {
+        self.retain(|key| !touches(key, collection));
+    }
+
+    /// Keep only the responses whose path satisfies `keep`
+    fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.entries.retain(|key, _| keep(key));
+        let entries = &self.entries;
+        self.order.retain(|key| entries.contains_key(key));
+        self.bytes = self.entries.values().map(Vec::len).sum();
+    }
+}
+
+/// `key` without its query
+fn base(key: &str) -> &str {
+    key.split_once('?').map_or(key, |(base, _)| base)
+}
+
+/// Whether the response of `key` may hold records of `collection`
+fn touches(key: &str, collection: &str) -> bool {
+    if base(key).split('/').any(|segment| segment == collection) {
+        return true;
+    }
+
+    // `_expand` and the id filters name the parent in the singular
+    let singular = collection.strip_suffix('s').unwrap_or(collection);
+    let id_filter = format!("{singular}Id");
+    let query = key.split_once('?').map_or("", |(_, query)| query);
+    query.split('&').any(|pair| {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        match name {
+            "_embed" | "_expand" => value == collection || value == singular,
+            _ => name == id_filter,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache_of(paths: &[&str]) -> ResponseCache {
+        let mut cache = ResponseCache::default();
+        for path in paths {
+            cache.insert(path, b"[]".to_vec());
+        }
+        cache
+    }
+
+    #[test]
+    fn write_drops_responses_embedding_or_expanding_the_collection() {
+        let mut cache = cache_of(&[
+            "/posts/1",
+            "/users/1/posts",
+            "/users?_embed=posts",
+            "/comments/1?_expand=post",
+            "/comments?postId=1",
+            "/comments/2",
+            "/albums?_embed=photos",
+        ]);
+
+        cache.invalidate_resource("posts");
+
+        for path in [
+            "/posts/1",
+            "/users/1/posts",
+            "/users?_embed=posts",
+            "/comments/1?_expand=post",
+            "/comments?postId=1",
+        ] {
+            assert!(cache.get(path).is_none(), "{path} kept");
+        }
+        assert!(cache.get("/comments/2").is_some());
+        assert!(cache.get("/albums?_embed=photos").is_some());
+    }
+
+    #[test]
+    fn comment_and_photo_writes_drop_embedding_parents() {
+        let mut cache = cache_of(&[
+            "/posts?_embed=comments",
+            "/posts/1?_embed=comments",
+            "/albums?_embed=photos",
+            "/posts/1",
+        ]);
+
+        cache.invalidate_resource("comments");
+        cache.invalidate_resource("photos");
+
+        assert!(cache.get("/posts?_embed=comments").is_none());
+        assert!(cache.get("/posts/1?_embed=comments").is_none());
+        assert!(cache.get("/albums?_embed=photos").is_none());
+        assert!(cache.get("/posts/1").is_some());
+    }
+
+    #[test]
+    fn invalidate_path_drops_every_query_of_the_path() {
+        let mut cache = cache_of(&["/posts", "/posts?userId=1", "/posts/1"]);
+
+        cache.invalidate_path("/posts");
+
+        assert!(cache.get("/posts").is_none());
+        assert!(cache.get("/posts?userId=1").is_none());
+        assert!(cache.get("/posts/1").is_some());
+    }
+
+    #[test]
+    fn oldest_responses_are_evicted_over_the_entry_cap() {
+        let mut cache = ResponseCache::default();
+        for id in 0..=MAX_ENTRIES {
+            cache.insert(&format!("/posts/{id}"), b"{}".to_vec());
+        }
+
+        assert!(cache.get("/posts/0").is_none());
+        assert!(cache.get(&format!("/posts/{MAX_ENTRIES}")).is_some());
+        assert_eq!(cache.entries.len(), MAX_ENTRIES);
+    }
+
+    #[test]
+    fn bodies_are_capped_by_size() {
+        let mut cache = ResponseCache::default();
+        cache.insert("/photos", vec![b' '; MAX_BYTES + 1]);
+        assert!(cache.get("/photos").is_none());
+
+        cache.insert("/a", vec![b' '; MAX_BYTES / 2 + 1]);
+        cache.insert("/b", vec![b' '; MAX_BYTES / 2 + 1]);
+        assert!(cache.get("/a").is_none());
+        assert!(cache.get("/b").is_some());
+        assert_eq!(cache.bytes, MAX_BYTES / 2 + 1);
+    }
+
+    #[test]
+    fn replacing_a_response_keeps_the_byte_count() {
+        let mut cache = ResponseCache::default();
+        cache.insert("/posts", vec![b' '; 10]);
+        cache.insert("/posts", vec![b' '; 4]);
+
+        assert_eq!(cache.bytes, 4);
+        assert_eq!(cache.order.len(), 1);
+    }
+}
@@ -1,493 +1,3008 @@
 use serde::Deserialize;
 use serde::Serialize;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+mod cache;
+mod models;
+mod path;
+mod query;
+
+use cache::ResponseCache;
+use models::{
+    AlbumSerde, CommentSerde, CreateAlbumSerde, CreateCommentSerde, CreatePhotoSerde,
+    CreateTodoSerde, CreateUserSerde, EmbeddedAlbumSerde, EmbeddedPostSerde, EmbeddedUserSerde,
+    ExpandedCommentSerde, ExpandedPostSerde, PatchAlbumSerde, PatchCommentSerde, PatchPhotoSerde,
+    PatchPostSerde, PatchTodoSerde, PatchUserSerde, PhotoSerde, PostBuilder, PostSerde, TodoSerde,
+    UserSerde,
+};
+use path::PathBuilder;
 
 wit_bindgen::generate!({
     world: "jsonplaceholder",
     path: "wit",
     generate_all,
+    // Records compare field by field, e.g. a fetched post against an echo
+    additional_derives: [PartialEq],
+    // Holds a resource, which has no equality
+    additional_derives_ignore: ["stream-error"],
 });
 
+use crate::wasi::clocks::monotonic_clock;
 use crate::wasi::http::outgoing_handler;
 use crate::wasi::http::types::*;
+use crate::wasi::io::poll;
+use crate::wasi::io::streams::{InputStream, StreamError};
 use exports::jsonplaceholder::api::jsonplaceholder_api::Guest as JsonplaceholderApi;
-use exports::jsonplaceholder::api::jsonplaceholder_api::NotFoundError;
+use exports::jsonplaceholder::api::jsonplaceholder_api::{
+    ComponentError, NotFoundError, ValidationError,
+};
 
 use crate::exports::jsonplaceholder::api::jsonplaceholder_api::{
-    Address, Album, Comment, Company, Geo, Photo, Post, Todo, User,
+    AlbumWithPhotos, Comment, CommentSortField, CommentWithPost, NewPost, NewUser, PageOptions,
+    PaginatedAlbums, PaginatedComments, PaginatedPhotos, PaginatedPosts, PaginatedTodos,
+    PaginatedUsers, Pagination, Post, PostQuery, PostSortField, PostWithComments, PostWithEmbedded,
+    PostWithUser, SortOptions, SortOrder, Todo, TodoSortField, User, UserPatch, UserProfile,
+    UserWithPosts, WriteResult,
 };
 
-//const BASE: &str = "https://jsonplaceholder.typicode.com";
+//
+// CONFIGURATION
+//
 
-/// Generic HTTP GET JSON - using synchronous blocking approach
-fn fetch_json<T: for<'a> Deserialize<'a>>(path: &str) -> Result<T, ()> {
-    // Construct the request
-    let request = OutgoingRequest::new(Fields::new());
+/// Authority used until `set-base-url` is called
+const DEFAULT_AUTHORITY: &str = "jsonplaceholder.typicode.com";
 
-    // Set method to GET
-    request.set_method(&Method::Get).map_err(|_| ())?;
+/// Scheme and authority every request is sent to
+struct BaseUrl {
+    authority: String,
+    https: bool,
+}
 
-    // Set scheme to HTTPS
-    request.set_scheme(Some(&Scheme::Https)).map_err(|_| ())?;
+/// Base URL set through `set-base-url`; `None` means the public API
+static BASE_URL: Mutex<Option<BaseUrl>> = Mutex::new(None);
 
-    // Set authority to jsonplaceholder domain
-    request
-        .set_authority(Some("jsonplaceholder.typicode.com"))
-        .map_err(|_| ())?;
+/// Current scheme and authority, falling back to the public API over HTTPS
+fn base_url() -> (Scheme, String) {
+    let base = BASE_URL.lock().unwrap_or_else(|e| e.into_inner());
+    match base.as_ref() {
+        Some(b) if !b.https => (Scheme::Http, b.authority.clone()),
+        Some(b) => (Scheme::Https, b.authority.clone()),
+        None => (Scheme::Https, DEFAULT_AUTHORITY.to_string()),
+    }
+}
 
-    // Set path with query (e.g., "/posts/1" or "/posts?userId=1")
-    request.set_path_with_query(Some(path)).map_err(|_| ())?;
+/// `User-Agent` sent until `set-user-agent` is called
+const DEFAULT_USER_AGENT: &str = concat!("jsonplaceholder-component/", env!("CARGO_PKG_VERSION"));
+
+/// `User-Agent` set through `set-user-agent`; `None` means the default
+static USER_AGENT: Mutex<Option<String>> = Mutex::new(None);
 
-    // No body for GET requests
-    let body = request.body().unwrap();
-    drop(body);
+/// Current `User-Agent` header value
+fn user_agent() -> String {
+    let agent = USER_AGENT.lock().unwrap_or_else(|e| e.into_inner());
+    agent.as_deref().unwrap_or(DEFAULT_USER_AGENT).to_string()
+}
 
-    // Send the request
-    let future_response = outgoing_handler::handle(request, None).map_err(|_| ())?;
+/// Retries of a failed GET allowed by `set-max-retries`; none by default
+static MAX_RETRIES: Mutex<u8> = Mutex::new(0);
 
-    // Block until response is ready
-    future_response.subscribe().block();
+/// Current retry budget for GET requests
+fn max_retries() -> u8 {
+    *MAX_RETRIES.lock().unwrap_or_else(|e| e.into_inner())
+}
 
-    // Get the response
-    let incoming_response = future_response
-        .get()
-        .ok_or(())? // Future not ready (shouldn't happen after block)
-        .map_err(|_| ())? // Error from the future
-        .map_err(|_| ())?; // HTTP error
+/// Longest wait for a response, set by `set-request-timeout-ms`; 0 waits
+/// indefinitely
+static REQUEST_TIMEOUT_MS: Mutex<u64> = Mutex::new(30_000);
 
-    // Check status code
-    if incoming_response.status() != 200 {
-        return Err(());
-    }
+/// Current response deadline in nanoseconds, `None` when disabled
+fn request_timeout_ns() -> Option<u64> {
+    let ms = *REQUEST_TIMEOUT_MS.lock().unwrap_or_else(|e| e.into_inner());
+    (ms > 0).then(|| ms.saturating_mul(1_000_000))
+}
 
-    // Read the response body
-    let body_stream = incoming_response.consume().map_err(|_| ())?;
-    let input_stream = body_stream.stream().map_err(|_| ())?;
+/// Resends of a GET answered 429, set by `set-rate-limit-retries`
+static RATE_LIMIT_RETRIES: Mutex<u8> = Mutex::new(3);
 
-    let mut bytes = Vec::new();
-    loop {
-        match input_stream.read(8192) {
-            Ok(chunk) => {
-                if chunk.is_empty() {
-                    break;
-                }
-                bytes.extend_from_slice(&chunk);
-            }
-            Err(_) => break,
-        }
+/// Longest wait a `Retry-After` header may ask for, set by
+/// `set-max-retry-after-secs`
+static MAX_RETRY_AFTER_SECS: Mutex<u64> = Mutex::new(60);
+
+/// Most results search-comments returns, set by `set-max-search-results`
+static MAX_SEARCH_RESULTS: Mutex<u32> = Mutex::new(500);
+
+/// Current cap on search-comments results
+fn max_search_results() -> u32 {
+    *MAX_SEARCH_RESULTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Bodies of successful GETs by path, so repeated reads skip the network.
+/// A body is read whole before it is parsed so that it can be kept, rather
+/// than parsed as it streams in; `ResponseCache` caps what stays resident.
+static CACHE: OnceLock<Mutex<ResponseCache>> = OnceLock::new();
+
+/// The response cache, locked
+fn cache() -> MutexGuard<'static, ResponseCache> {
+    CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Cached body of a GET of `path`, if any
+fn cached(path: &str) -> Option<Vec<u8>> {
+    cache().get(path).map(<[u8]>::to_vec)
+}
+
+/// Remember the body of a GET of `path`
+fn cache_store(path: &str, bytes: Vec<u8>) {
+    cache().insert(path, bytes);
+}
+
+/// Drop the cached GETs of `path`, with or without a query
+fn invalidate_path(path: &str) {
+    cache().invalidate_path(path);
+}
+
+/// Drop every cached GET touching the collection `path` is in, e.g. after a
+/// write to `/posts/1` the record, `/posts?userId=1`, `/users/1/posts` and
+/// `/users?_embed=posts`
+fn invalidate_resource(path: &str) {
+    if let Some(collection) = path.split(['/', '?']).find(|s| !s.is_empty()) {
+        cache().invalidate_resource(collection);
     }
+}
 
-    // Parse JSON
-    serde_json::from_slice(&bytes).map_err(|_| ())
+/// Response size allowed until `set-max-response-bytes` is called
+const DEFAULT_MAX_RESPONSE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Largest response body read, set by `set-max-response-bytes`
+static MAX_RESPONSE_BYTES: Mutex<u64> = Mutex::new(DEFAULT_MAX_RESPONSE_BYTES);
+
+/// Current response size limit in bytes
+fn max_response_bytes() -> u64 {
+    *MAX_RESPONSE_BYTES.lock().unwrap_or_else(|e| e.into_inner())
 }
 
 //
-// DATA MODELS FOR SERDE
+// HTTP
 //
 
-#[derive(Deserialize, Serialize)]
-struct GeoSerde {
-    lat: String,
-    lng: String,
+/// Build a `not-found` error for a request to `path` answered with `status`
+fn not_found(status: u16, path: &str) -> ComponentError {
+    ComponentError::NotFound(NotFoundError {
+        message: format!("{path} not found"),
+        status,
+        path: path.to_string(),
+    })
 }
 
-impl From<GeoSerde> for Geo {
-    fn from(g: GeoSerde) -> Self {
-        Geo {
-            lat: g.lat,
-            lng: g.lng,
-        }
+/// Report a failed write to a single resource as not found.
+/// jsonplaceholder answers 500 rather than 404 for writes to unknown ids.
+fn missing_on_write(e: ComponentError, path: &str) -> ComponentError {
+    match e {
+        ComponentError::ServerError(500) => not_found(500, path),
+        e => e,
     }
 }
 
-#[derive(Deserialize, Serialize)]
-struct AddressSerde {
-    street: String,
-    suite: String,
-    city: String,
-    zipcode: String,
-    geo: GeoSerde,
-}
-
-impl From<AddressSerde> for Address {
-    fn from(a: AddressSerde) -> Self {
-        Address {
-            street: a.street,
-            suite: a.suite,
-            city: a.city,
-            zipcode: a.zipcode,
-            geo: a.geo.into(),
-        }
+/// Classify a status code the caller of `path` did not expect
+fn status_error(status: u16, path: &str) -> ComponentError {
+    match status {
+        404 | 410 => not_found(status, path),
+        429 => ComponentError::RateLimited,
+        _ => ComponentError::ServerError(status),
     }
 }
 
-#[derive(Deserialize, Serialize)]
-struct CompanySerde {
-    name: String,
-    #[serde(rename = "catchPhrase")]
-    catch_phrase: String,
-    bs: String,
+/// Classify a status code the caller of a write to `path` did not expect.
+/// 400 and 422 are the server rejecting the payload, described by `body`.
+fn write_error(status: u16, path: &str, body: &[u8]) -> ComponentError {
+    match status {
+        400 | 422 => ComponentError::ValidationError(ValidationError {
+            status,
+            detail: error_detail(body),
+        }),
+        _ => status_error(status, path),
+    }
 }
 
-impl From<CompanySerde> for Company {
-    fn from(c: CompanySerde) -> Self {
-        Company {
-            name: c.name,
-            catch_phrase: c.catch_phrase,
-            bs: c.bs,
+/// Human-readable detail of an error body: the `message` (or `error`) of a
+/// JSON object, otherwise the body text itself
+fn error_detail(body: &[u8]) -> String {
+    if let Ok(serde_json::Value::Object(map)) = serde_json::from_slice(body) {
+        let message = ["message", "error"]
+            .iter()
+            .find_map(|key| map.get(*key).and_then(|v| v.as_str()));
+        if let Some(message) = message {
+            return message.to_string();
         }
     }
+
+    String::from_utf8_lossy(body).trim().to_string()
 }
 
-#[derive(Deserialize, Serialize)]
-struct PostSerde {
-    id: u64,
-    #[serde(rename = "userId")]
-    user_id: u64,
-    title: String,
-    body: String,
+/// Build an outgoing request against the configured base URL
+fn build_request(
+    method: &Method,
+    headers: Fields,
+    path: &str,
+) -> Result<OutgoingRequest, ComponentError> {
+    let request = OutgoingRequest::new(headers);
+
+    request
+        .set_method(method)
+        .map_err(|_| ComponentError::Network)?;
+
+    // Set scheme and authority from the configured base URL
+    let (scheme, authority) = base_url();
+    request
+        .set_scheme(Some(&scheme))
+        .map_err(|_| ComponentError::Network)?;
+    request
+        .set_authority(Some(&authority))
+        .map_err(|_| ComponentError::Network)?;
+
+    // Set path with query (e.g., "/posts/1" or "/posts?userId=1")
+    request
+        .set_path_with_query(Some(path))
+        .map_err(|_| ComponentError::Network)?;
+
+    Ok(request)
 }
 
-impl From<PostSerde> for Post {
-    fn from(p: PostSerde) -> Self {
-        Post {
-            id: p.id,
-            user_id: p.user_id,
-            title: p.title,
-            body: p.body,
-        }
+/// Block until the response to a sent request is ready, or fail with
+/// `Timeout` once the request timeout passes; the request is then dropped
+fn await_response(
+    future_response: FutureIncomingResponse,
+) -> Result<IncomingResponse, ComponentError> {
+    if !block_on_with_timeout(&future_response.subscribe(), request_timeout_ns()) {
+        return Err(ComponentError::Timeout);
     }
+
+    future_response
+        .get()
+        .ok_or(ComponentError::Network)? // Future not ready (shouldn't happen after block)
+        .map_err(|_| ComponentError::Network)? // Error from the future
+        .map_err(|_| ComponentError::Network) // HTTP error
 }
 
-#[derive(Deserialize, Serialize)]
-struct UserSerde {
-    id: u64,
-    name: String,
-    username: String,
-    email: String,
-    phone: String,
-    website: String,
-    company: CompanySerde,
-    address: AddressSerde,
+/// Block until `ready` fires or `timeout_ns` passes; whether `ready` fired
+fn block_on_with_timeout(ready: &poll::Pollable, timeout_ns: Option<u64>) -> bool {
+    let Some(timeout_ns) = timeout_ns else {
+        ready.block();
+        return true;
+    };
+
+    let deadline = monotonic_clock::subscribe_duration(timeout_ns);
+    poll::poll(&[ready, &deadline]).contains(&0)
+}
+
+/// Where a response body is read from: a WASI input stream, or a stand-in
+/// in tests
+trait InputSource {
+    /// Up to `len` bytes, blocking until some are available; `None` once the
+    /// body has ended
+    fn read(&self, len: u64) -> Result<Option<Vec<u8>>, ComponentError>;
 }
 
-impl From<UserSerde> for User {
-    fn from(u: UserSerde) -> Self {
-        User {
-            username: u.username,
-            id: u.id,
-            name: u.name,
-            email: u.email,
-            phone: u.phone,
-            website: u.website,
-            company: u.company.into(),
-            address: u.address.into(),
+impl InputSource for InputStream {
+    fn read(&self, len: u64) -> Result<Option<Vec<u8>>, ComponentError> {
+        match self.blocking_read(len) {
+            Ok(chunk) => Ok(Some(chunk)),
+            Err(StreamError::Closed) => Ok(None),
+            Err(_) => Err(ComponentError::Network),
         }
     }
 }
 
-#[derive(Deserialize, Serialize)]
-struct CommentSerde {
-    id: u64,
-    #[serde(rename = "postId")]
-    post_id: u64,
-    name: String,
-    email: String,
-    body: String,
+/// Read the whole response body into memory
+fn read_body(incoming_response: &IncomingResponse) -> Result<Vec<u8>, ComponentError> {
+    let body_stream = incoming_response
+        .consume()
+        .map_err(|_| ComponentError::Network)?;
+    let input_stream = body_stream.stream().map_err(|_| ComponentError::Network)?;
+
+    read_all(&input_stream, max_response_bytes())
 }
 
-impl From<CommentSerde> for Comment {
-    fn from(c: CommentSerde) -> Self {
-        Comment {
-            id: c.id,
-            post_id: c.post_id,
-            name: c.name,
-            email: c.email,
-            body: c.body,
+/// Read `source` to its end, failing once more than `limit` bytes arrive
+fn read_all(source: &impl InputSource, limit: u64) -> Result<Vec<u8>, ComponentError> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = source.read(8192)? {
+        bytes.extend_from_slice(&chunk);
+        if u64::try_from(bytes.len()).unwrap_or(u64::MAX) > limit {
+            return Err(ComponentError::ResponseTooLarge(limit));
         }
     }
+
+    Ok(bytes)
 }
 
-#[derive(Deserialize, Serialize)]
-struct AlbumSerde {
-    id: u64,
-    #[serde(rename = "userId")]
-    user_id: u64,
-    title: String,
+/// The body stream of a response, kept together with the body it belongs to
+struct ResponseStream {
+    // Declared first so it is dropped before the body it belongs to
+    stream: InputStream,
+    _body: IncomingBody,
 }
 
-impl From<AlbumSerde> for Album {
-    fn from(a: AlbumSerde) -> Self {
-        Album {
-            id: a.id,
-            user_id: a.user_id,
-            title: a.title,
-        }
+impl ResponseStream {
+    fn new(incoming_response: &IncomingResponse) -> Result<Self, ComponentError> {
+        let body = incoming_response
+            .consume()
+            .map_err(|_| ComponentError::Network)?;
+        let stream = body.stream().map_err(|_| ComponentError::Network)?;
+
+        Ok(ResponseStream {
+            stream,
+            _body: body,
+        })
     }
 }
 
-#[derive(Deserialize, Serialize)]
-struct PhotoSerde {
-    id: u64,
-    #[serde(rename = "albumId")]
-    album_id: u64,
-    title: String,
-    url: String,
-    #[serde(rename = "thumbnailUrl")]
-    thumbnail_url: String,
+impl InputSource for ResponseStream {
+    fn read(&self, len: u64) -> Result<Option<Vec<u8>>, ComponentError> {
+        InputSource::read(&self.stream, len)
+    }
+}
+
+/// `std::io::Read` over a response body, so JSON can be parsed while it
+/// arrives instead of after buffering the whole payload
+struct BoundedReader<'a, S> {
+    source: &'a S,
+    /// Bytes that may still be read before the response is too large
+    remaining: u64,
 }
 
-impl From<PhotoSerde> for Photo {
-    fn from(p: PhotoSerde) -> Self {
-        Photo {
-            id: p.id,
-            album_id: p.album_id,
-            thumbnail_url: p.thumbnail_url,
-            title: p.title,
-            url: p.url,
+impl<S: InputSource> std::io::Read for BoundedReader<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.remaining == 0 {
+            // Only too large if there is more to come
+            return match self.source.read(1) {
+                Ok(Some(chunk)) if !chunk.is_empty() => {
+                    Err(std::io::ErrorKind::FileTooLarge.into())
+                }
+                Ok(_) => Ok(0),
+                Err(_) => Err(std::io::ErrorKind::BrokenPipe.into()),
+            };
+        }
+
+        let len = u64::try_from(buf.len())
+            .unwrap_or(u64::MAX)
+            .min(self.remaining);
+        loop {
+            match self.source.read(len) {
+                // An empty read is not the end of the body; that is `None`
+                Ok(Some(chunk)) if chunk.is_empty() && !buf.is_empty() => continue,
+                Ok(Some(chunk)) => {
+                    buf[..chunk.len()].copy_from_slice(&chunk);
+                    self.remaining -= u64::try_from(chunk.len()).unwrap_or(u64::MAX);
+                    return Ok(chunk.len());
+                }
+                Ok(None) => return Ok(0),
+                Err(_) => return Err(std::io::ErrorKind::BrokenPipe.into()),
+            }
         }
     }
 }
 
-#[derive(Deserialize, Serialize)]
-struct TodoSerde {
-    id: u64,
-    #[serde(rename = "userId")]
-    user_id: u64,
-    title: String,
-    completed: bool,
+/// Parse the response body as JSON while streaming it in
+fn parse_body<T: for<'a> Deserialize<'a>>(
+    incoming_response: &IncomingResponse,
+) -> Result<T, ComponentError> {
+    parse_bounded(
+        &ResponseStream::new(incoming_response)?,
+        max_response_bytes(),
+    )
 }
 
-impl From<TodoSerde> for Todo {
-    fn from(t: TodoSerde) -> Self {
-        Todo {
-            id: t.id,
-            user_id: t.user_id,
-            title: t.title,
-            completed: t.completed,
-        }
-    }
+/// Parse JSON streamed from `source`, failing once more than `limit` bytes
+/// arrive. Only the reader's buffer is held, never the whole body.
+fn parse_bounded<T: for<'a> Deserialize<'a>>(
+    source: &impl InputSource,
+    limit: u64,
+) -> Result<T, ComponentError> {
+    let reader = std::io::BufReader::new(BoundedReader {
+        source,
+        remaining: limit,
+    });
+    serde_json::from_reader(reader).map_err(|e| match e.io_error_kind() {
+        Some(std::io::ErrorKind::FileTooLarge) => ComponentError::ResponseTooLarge(limit),
+        Some(_) => ComponentError::Network,
+        None => ComponentError::Parse,
+    })
 }
 
-//
-// IMPLEMENTATION OF THE WIT INTERFACE
-//
+/// Where `write_all` sends a request body: a WASI output stream, or a
+/// stand-in in tests
+trait OutputSink {
+    /// Bytes that may be written now; 0 until the sink is ready
+    fn check_write(&self) -> Result<u64, ComponentError>;
+    fn write(&self, chunk: &[u8]) -> Result<(), ComponentError>;
+    fn flush(&self) -> Result<(), ComponentError>;
+    /// Block until more bytes may be written or a flush has completed
+    fn wait(&self);
+}
 
-struct ApiImpl;
+impl OutputSink for OutputStream {
+    fn check_write(&self) -> Result<u64, ComponentError> {
+        OutputStream::check_write(self).map_err(|_| ComponentError::Network)
+    }
 
-impl JsonplaceholderApi for ApiImpl {
-    fn get_posts(user_id: u64) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Post> {
-        fetch_json::<Vec<PostSerde>>(&format!("/posts?userId={user_id}"))
-            .unwrap_or_default()
-            .into_iter()
-            .map(|p| p.into())
-            .collect()
+    fn write(&self, chunk: &[u8]) -> Result<(), ComponentError> {
+        OutputStream::write(self, chunk).map_err(|_| ComponentError::Network)
     }
 
-    fn get_post(
-        id: u64,
-    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Post, NotFoundError> {
-        fetch_json::<PostSerde>(&format!("/posts/{id}"))
-            .map(|p| p.into())
-            .map_err(|_| NotFoundError {
-                message: "Not found".to_string(),
-            })
+    fn flush(&self) -> Result<(), ComponentError> {
+        OutputStream::flush(self).map_err(|_| ComponentError::Network)
     }
 
-    fn get_post_comments(
-        id: u64,
-    ) -> Result<Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Comment>, NotFoundError>
-    {
-        fetch_json::<Vec<CommentSerde>>(&format!("/posts/{id}/comments"))
-            .map(|v| v.into_iter().map(|c| c.into()).collect())
-            .map_err(|_| NotFoundError {
-                message: "Not found".to_string(),
-            })
+    fn wait(&self) {
+        self.subscribe().block();
     }
+}
 
-    fn get_comments(
-        id: Option<u64>,
-        post_id: Option<u64>,
-    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Comment> {
-        let mut q = vec![];
-        if let Some(i) = id {
-            q.push(format!("id={i}"));
-        }
-        if let Some(p) = post_id {
-            q.push(format!("postId={p}"));
+/// Write all of `bytes` to `stream`, never exceeding the budget granted by
+/// `check-write`, then flush and wait for the flush to complete
+fn write_all(stream: &impl OutputSink, mut bytes: &[u8]) -> Result<(), ComponentError> {
+    while !bytes.is_empty() {
+        let budget = stream.check_write()?;
+        if budget == 0 {
+            stream.wait();
+            continue;
         }
 
-        let query = if q.is_empty() {
-            "".to_string()
-        } else {
-            format!("?{}", q.join("&"))
-        };
+        let len = usize::try_from(budget).map_or(bytes.len(), |b| b.min(bytes.len()));
+        let (chunk, rest) = bytes.split_at(len);
+        stream.write(chunk)?;
+        bytes = rest;
+    }
 
-        fetch_json::<Vec<CommentSerde>>(&format!("/comments{query}"))
-            .unwrap_or_default()
-            .into_iter()
-            .map(|c| c.into())
-            .collect()
+    stream.flush()?;
+    stream.wait();
+    // A failed flush is reported by the next `check-write`
+    stream.check_write()?;
+
+    Ok(())
+}
+
+/// Send a request accepting JSON and wait for its response. `payload`, when
+/// given, is written as a JSON request body.
+fn send_request(
+    method: &Method,
+    path: &str,
+    payload: Option<&[u8]>,
+) -> Result<IncomingResponse, ComponentError> {
+    let headers = Fields::new();
+    // Ask for JSON explicitly; some proxies default to HTML or XML
+    headers
+        .set("accept", &[b"application/json".to_vec()])
+        .map_err(|_| ComponentError::Network)?;
+    headers
+        .set("user-agent", &[user_agent().into_bytes()])
+        .map_err(|_| ComponentError::Network)?;
+    if payload.is_some() {
+        headers
+            .set("content-type", &[b"application/json".to_vec()])
+            .map_err(|_| ComponentError::Network)?;
     }
 
-    fn get_comment(
-        id: u64,
-    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Comment, NotFoundError> {
-        fetch_json::<CommentSerde>(&format!("/comments/{id}"))
-            .map(|c| c.into())
-            .map_err(|_| NotFoundError {
-                message: "Not found".to_string(),
-            })
+    // Whatever was cached about the written resource may now be stale
+    if !matches!(method, Method::Get) {
+        invalidate_resource(path);
     }
 
-    fn get_albums(
-        id: Option<u64>,
-        user_id: Option<u64>,
-    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Album> {
-        let mut q = vec![];
-        if let Some(i) = id {
-            q.push(format!("id={i}"));
-        }
-        if let Some(u) = user_id {
-            q.push(format!("userId={u}"));
-        }
+    let request = build_request(method, headers, path)?;
+    let body = request.body().map_err(|_| ComponentError::Network)?;
 
-        let query = if q.is_empty() {
-            "".to_string()
-        } else {
-            format!("?{}", q.join("&"))
-        };
+    // Send the request, then stream the payload into its body
+    let future_response =
+        outgoing_handler::handle(request, None).map_err(|_| ComponentError::Network)?;
+    if let Some(payload) = payload {
+        let output_stream = body.write().map_err(|_| ComponentError::Network)?;
+        write_all(&output_stream, payload)?;
+    }
 
-        fetch_json::<Vec<AlbumSerde>>(&format!("/albums{query}"))
-            .unwrap_or_default()
-            .into_iter()
-            .map(|a| a.into())
-            .collect()
+    // Always finish the body, even when empty; some hosts reject a body that
+    // is merely dropped
+    OutgoingBody::finish(body, None).map_err(|_| ComponentError::Network)?;
+
+    await_response(future_response)
+}
+
+/// First value of the response header `name`, if it is valid UTF-8
+fn header_value(incoming_response: &IncomingResponse, name: &str) -> Option<String> {
+    let headers = incoming_response.headers();
+    let values = headers.get(name);
+    values
+        .into_iter()
+        .next()
+        .and_then(|v| String::from_utf8(v).ok())
+}
+
+/// Generic HTTP JSON write - sends `body` as JSON and parses the JSON of a
+/// 200 or 201 response, reporting which status and `Location` came back. A
+/// 204 carries no record to return, so it is an unexpected status here.
+fn send_json<Req: Serialize, Resp: for<'a> Deserialize<'a>>(
+    method: &Method,
+    path: &str,
+    body: &Req,
+) -> Result<(Resp, WriteResult), ComponentError> {
+    let payload = serde_json::to_vec(body).map_err(|_| ComponentError::Parse)?;
+
+    let incoming_response = send_request(method, path, Some(&payload))?;
+
+    let status = incoming_response.status();
+    let Some(result) = write_outcome(status, header_value(&incoming_response, "location")) else {
+        let bytes = read_body(&incoming_response)?;
+        return Err(write_error(status, path, &bytes));
+    };
+
+    parse_body(&incoming_response).map(|value| (value, result))
+}
+
+/// Outcome of a write answered with `status` and the `Location` header
+/// `location`; `None` unless the status carries a record back.
+/// jsonplaceholder answers creates with 201 Created.
+fn write_outcome(status: u16, location: Option<String>) -> Option<WriteResult> {
+    matches!(status, 200 | 201).then_some(WriteResult { status, location })
+}
+
+/// GET `path`, answering with whatever status the server settles on. A 429
+/// is sent again, up to `set-rate-limit-retries` times, after the wait its
+/// `Retry-After` header asks for.
+fn send_get(path: &str) -> Result<IncomingResponse, ComponentError> {
+    let retries = *RATE_LIMIT_RETRIES.lock().unwrap_or_else(|e| e.into_inner());
+    let mut attempt = 0;
+    loop {
+        let incoming_response = send_request(&Method::Get, path, None)?;
+        if incoming_response.status() == 429 && attempt < retries {
+            monotonic_clock::subscribe_duration(retry_after_ns(&incoming_response)).block();
+            attempt += 1;
+            continue;
+        }
+
+        return Ok(incoming_response);
     }
+}
 
-    fn get_album(
-        id: u64,
-    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Album, NotFoundError> {
-        fetch_json::<AlbumSerde>(&format!("/albums/{id}"))
-            .map(|a| a.into())
-            .map_err(|_| NotFoundError {
-                message: "Not found".to_string(),
-            })
+/// GET `path` and read the raw body of a 200 response. The body is kept
+/// whole, unlike `parse_body`, so it can be cached.
+fn fetch_bytes(path: &str) -> Result<Vec<u8>, ComponentError> {
+    let incoming_response = send_get(path)?;
+
+    let status = incoming_response.status();
+    if status != 200 {
+        return Err(status_error(status, path));
     }
 
-    fn get_album_photos(
-        id: u64,
-    ) -> Result<Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Photo>, NotFoundError> {
-        fetch_json::<Vec<PhotoSerde>>(&format!("/albums/{id}/photos"))
-            .map(|v| v.into_iter().map(|p| p.into()).collect())
-            .map_err(|_| NotFoundError {
-                message: "Not found".to_string(),
-            })
+    read_body(&incoming_response)
+}
+
+/// Wait asked for by the `Retry-After` seconds of a 429, capped by
+/// `set-max-retry-after-secs`; one second when it is missing or a date
+fn retry_after_ns(incoming_response: &IncomingResponse) -> u64 {
+    let max_secs = *MAX_RETRY_AFTER_SECS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let secs = header_value(incoming_response, "retry-after")
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(1);
+    secs.min(max_secs).saturating_mul(1_000_000_000)
+}
+
+/// Generic HTTP GET JSON - using synchronous blocking approach, retried as
+/// configured through `set-max-retries`. The error keeps the status of a
+/// failed response: 404/410 are `not-found`, 429 `rate-limited` and any
+/// other unexpected status `server-error(status)`, so callers can pass it on
+/// unchanged.
+fn fetch_json<T: for<'a> Deserialize<'a>>(path: &str) -> Result<T, ComponentError> {
+    fetch_json_with_retry(path, max_retries())
+}
+
+/// Whether a failed request may succeed when simply sent again. A 429 is
+/// not: `fetch_bytes` has already resent it as `Retry-After` asked.
+fn is_transient(e: &ComponentError) -> bool {
+    matches!(
+        e,
+        ComponentError::Network
+            | ComponentError::Timeout
+            | ComponentError::ServerError(500 | 502 | 503 | 504)
+    )
+}
+
+/// GET `path` like `fetch_json`, retrying transient failures up to
+/// `max_retries` times
+fn fetch_json_with_retry<T: for<'a> Deserialize<'a>>(
+    path: &str,
+    max_retries: u8,
+) -> Result<T, ComponentError> {
+    if let Some(bytes) = cached(path) {
+        return serde_json::from_slice(&bytes).map_err(|_| ComponentError::Parse);
     }
 
-    fn get_photos(
-        id: Option<u64>,
-        album_id: Option<u64>,
-    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Photo> {
-        let mut q = vec![];
-        if let Some(i) = id {
-            q.push(format!("id={i}"));
+    let bytes = with_retry(max_retries, || fetch_bytes(path))?;
+    let value = serde_json::from_slice(&bytes).map_err(|_| ComponentError::Parse)?;
+    cache_store(path, bytes);
+    Ok(value)
+}
+
+/// Run `attempt` until it succeeds or fails for good, retrying transient
+/// failures up to `max_retries` times. The delay doubles from 100 ms
+/// between attempts.
+fn with_retry<T>(
+    max_retries: u8,
+    mut attempt: impl FnMut() -> Result<T, ComponentError>,
+) -> Result<T, ComponentError> {
+    let mut delay_ns: u64 = 100_000_000;
+    let mut retries = 0;
+    loop {
+        match attempt() {
+            Err(e) if retries < max_retries && is_transient(&e) => {
+                monotonic_clock::subscribe_duration(delay_ns).block();
+                delay_ns = delay_ns.saturating_mul(2);
+                retries += 1;
+            }
+            result => return result,
         }
-        if let Some(a) = album_id {
-            q.push(format!("albumId={a}"));
+    }
+}
+
+/// Fetch the single record with `id` at `path`. A record carrying any other
+/// id (say, from a misbehaving cache in between) is a parse failure.
+fn fetch_record<T: for<'a> Deserialize<'a>>(path: &str, id: u64) -> Result<T, ComponentError> {
+    let value = fetch_json::<serde_json::Value>(path)?;
+    if value.get("id").and_then(serde_json::Value::as_u64) != Some(id) {
+        return Err(ComponentError::Parse);
+    }
+
+    serde_json::from_value(value).map_err(|_| ComponentError::Parse)
+}
+
+/// Whether a GET of `path` answers 200; false on any other status or when
+/// the request fails. GET rather than HEAD, which not every route supports.
+/// Transient failures are retried like `fetch_json`.
+fn resource_exists(path: &str) -> bool {
+    with_retry(max_retries(), || {
+        let status = send_get(path)?.status();
+        if status == 200 {
+            return Ok(true);
         }
 
-        let query = if q.is_empty() {
-            "".to_string()
+        let e = status_error(status, path);
+        if is_transient(&e) {
+            Err(e)
         } else {
-            format!("?{}", q.join("&"))
-        };
+            Ok(false)
+        }
+    })
+    .unwrap_or(false)
+}
 
-        fetch_json::<Vec<PhotoSerde>>(&format!("/photos{query}"))
-            .unwrap_or_default()
-            .into_iter()
-            .map(|p| p.into())
-            .collect()
+/// GET `path` like `fetch_json`, uncached, also returning the
+/// `X-Total-Count` header json-server sends with paged lists; `None` when it
+/// is missing or not a number
+fn fetch_with_total<T: for<'a> Deserialize<'a>>(
+    path: &str,
+) -> Result<(T, Option<u64>), ComponentError> {
+    with_retry(max_retries(), || {
+        let incoming_response = send_get(path)?;
+
+        let status = incoming_response.status();
+        if status != 200 {
+            return Err(status_error(status, path));
+        }
+
+        let total =
+            header_value(&incoming_response, "x-total-count").and_then(|v| v.trim().parse().ok());
+        parse_body(&incoming_response).map(|value| (value, total))
+    })
+}
+
+/// Generic HTTP POST JSON - serializes `payload` as the request body and
+/// returns the created record with the reported status and location
+fn post_json<B: Serialize, T: for<'a> Deserialize<'a>>(
+    path: &str,
+    payload: &B,
+) -> Result<(T, WriteResult), ComponentError> {
+    send_json(&Method::Post, path, payload)
+}
+
+/// Generic HTTP PUT JSON - replaces the resource at `path` with `payload`
+fn put_json<B: Serialize, T: for<'a> Deserialize<'a>>(
+    path: &str,
+    payload: &B,
+) -> Result<T, ComponentError> {
+    send_json(&Method::Put, path, payload).map(|(value, _)| value)
+}
+
+/// Generic HTTP PATCH JSON - merges `payload` into the resource at `path`
+fn patch_json<B: Serialize, T: for<'a> Deserialize<'a>>(
+    path: &str,
+    payload: &B,
+) -> Result<T, ComponentError> {
+    send_json(&Method::Patch, path, payload).map(|(value, _)| value)
+}
+
+/// Generic HTTP DELETE - succeeds on 200 OK or 204 No Content with an
+/// empty (or `{}`) body
+fn delete_resource(path: &str) -> Result<(), ComponentError> {
+    let incoming_response = send_request(&Method::Delete, path, None)?;
+
+    let bytes = read_body(&incoming_response)?;
+    expect_empty_response(path, incoming_response.status(), &bytes)
+}
+
+/// Check a response from `path` that should carry no payload: 200 or 204
+/// with a blank or `{}` body
+fn expect_empty_response(path: &str, status: u16, body: &[u8]) -> Result<(), ComponentError> {
+    if status != 200 && status != 204 {
+        return Err(write_error(status, path, body));
     }
 
-    fn get_photo(
-        id: u64,
-    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Photo, NotFoundError> {
-        fetch_json::<PhotoSerde>(&format!("/photos/{id}"))
-            .map(|p| p.into())
-            .map_err(|_| NotFoundError {
-                message: "Not found".to_string(),
-            })
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(());
     }
 
-    fn get_todos(
-        id: Option<u64>,
-        user_id: Option<u64>,
-    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Todo> {
-        let mut q = vec![];
-        if let Some(i) = id {
-            q.push(format!("id={i}"));
-        }
-        if let Some(u) = user_id {
-            q.push(format!("userId={u}"));
-        }
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(serde_json::Value::Object(map)) if map.is_empty() => Ok(()),
+        _ => Err(ComponentError::Parse),
+    }
+}
 
-        let query = if q.is_empty() {
-            "".to_string()
-        } else {
-            format!("?{}", q.join("&"))
-        };
+/// Deserialize a record echoed by a write to `path`. jsonplaceholder answers
+/// some writes to unknown ids with 200 and `{}`, which is reported as not
+/// found instead of being parsed into a zeroed record.
+fn echoed_record<T: for<'a> Deserialize<'a>>(
+    value: serde_json::Value,
+    path: &str,
+) -> Result<T, ComponentError> {
+    if value.as_object().is_some_and(|map| map.is_empty()) {
+        return Err(not_found(200, path));
+    }
 
-        fetch_json::<Vec<TodoSerde>>(&format!("/todos{query}"))
-            .unwrap_or_default()
-            .into_iter()
-            .map(|t| t.into())
-            .collect()
+    serde_json::from_value(value).map_err(|_| ComponentError::Parse)
+}
+
+/// Whether `authority` is a bare `host[:port]`: no whitespace or control
+/// characters, and no path, query, fragment or userinfo
+fn is_valid_authority(authority: &str) -> bool {
+    !authority.is_empty()
+        && !authority
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || matches!(c, '/' | '?' | '#' | '@'))
+}
+
+/// Whether `email` looks like `local@domain` and is safe to send as-is
+fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+
+    !local.is_empty()
+        && !domain.is_empty()
+        && !domain.contains('@')
+        && !email.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+/// Check that `url` is an absolute http(s) URL with a host; `field` names the
+/// value in the error
+fn check_http_url(field: &str, url: &str) -> Result<(), ComponentError> {
+    let invalid = |reason: &str| {
+        Err(ComponentError::InvalidInput(format!(
+            "Invalid {field} {url:?}: {reason}"
+        )))
+    };
+
+    if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return invalid("contains whitespace or control characters");
     }
 
-    fn get_todo(
-        id: u64,
-    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Todo, NotFoundError> {
-        fetch_json::<TodoSerde>(&format!("/todos/{id}"))
-            .map(|t| t.into())
-            .map_err(|_| NotFoundError {
-                message: "Not found".to_string(),
-            })
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return invalid("missing scheme");
+    };
+    if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+        return invalid("scheme must be http or https");
     }
 
-    fn get_users(
-        id: Option<u64>,
-        email: Option<String>,
-    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::User> {
-        let mut q = vec![];
-        if let Some(i) = id {
-            q.push(format!("id={i}"));
-        }
-        if let Some(e) = email {
-            q.push(format!("email={e}"));
-        }
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+    // Bracketed IPv6 hosts contain colons of their own
+    let (host, port) = match host_port.strip_prefix('[') {
+        Some(v6) => match v6.split_once(']') {
+            Some((host, tail)) => (host, tail.strip_prefix(':').unwrap_or(tail)),
+            None => return invalid("unterminated IPv6 host"),
+        },
+        None => host_port.split_once(':').unwrap_or((host_port, "")),
+    };
+    if !port.bytes().all(|b| b.is_ascii_digit()) {
+        return invalid("port must be numeric");
+    }
+    if host.is_empty() {
+        return invalid("missing host");
+    }
 
-        let query = if q.is_empty() {
-            "".to_string()
-        } else {
-            format!("?{}", q.join("&"))
-        };
+    Ok(())
+}
 
-        fetch_json::<Vec<UserSerde>>(&format!("/users{query}"))
-            .unwrap_or_default()
-            .into_iter()
-            .map(|u| u.into())
-            .collect()
+/// Query pairs for `options`: `_page` and `_limit`, or `_start` and `_end`
+/// for a range, each only when set. Mixing both styles is rejected.
+fn page_option_pairs(
+    options: &PageOptions,
+) -> Result<[(&'static str, Option<String>); 4], ComponentError> {
+    let paging = options.page.is_some() || options.limit.is_some();
+    let range = options.start.is_some() || options.end.is_some();
+    if paging && range {
+        return Err(ComponentError::InvalidInput(
+            "page/limit and start/end cannot be combined".to_string(),
+        ));
     }
 
-    fn get_user(
-        id: u64,
-    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::User, NotFoundError> {
-        fetch_json::<UserSerde>(&format!("/users/{id}"))
-            .map(|u| u.into())
-            .map_err(|_| NotFoundError {
-                message: "Not found".to_string(),
-            })
-    }
+    Ok([
+        ("_page", options.page.map(|v| v.to_string())),
+        ("_limit", options.limit.map(|v| v.to_string())),
+        ("_start", options.start.map(|v| v.to_string())),
+        ("_end", options.end.map(|v| v.to_string())),
+    ])
 }
 
-__export_jsonplaceholder_impl!(ApiImpl);
+/// Path of one page or range of `collection`, optionally filtered on
+/// `filter`, e.g. `/comments?postId=7&_start=20&_end=40`
+fn paged_path(
+    collection: &str,
+    filter: (&str, Option<u64>),
+    options: &PageOptions,
+) -> Result<String, ComponentError> {
+    let (key, value) = filter;
+    let [page, limit, start, end] = page_option_pairs(options)?;
+    Ok(PathBuilder::resource(collection)
+        .pairs(&[(key, value.map(|v| v.to_string())), page, limit, start, end])
+        .build())
+}
+
+/// One page of a list with the paging it was fetched with
+struct Paged<T> {
+    items: Vec<T>,
+    total: Option<u64>,
+    page: u32,
+    limit: u32,
+}
+
+/// List `collection` (e.g. `posts`) one page or range at a time,
+/// optionally filtered on `filter`. Shared by the `*-paged` functions so page
+/// options behave the same on every resource. An unset page is reported as 1
+/// and an unset limit as the number of items.
+fn fetch_paged<S: for<'a> Deserialize<'a>, T: TryFrom<S>>(
+    collection: &str,
+    filter: (&str, Option<u64>),
+    options: PageOptions,
+) -> Result<Paged<T>, ComponentError> {
+    let path = paged_path(collection, filter, &options)?;
+    let (items, total) = fetch_with_total::<Vec<S>>(&path)?;
+    let items = items
+        .into_iter()
+        .map(|s| T::try_from(s).map_err(|_| ComponentError::Parse))
+        .collect::<Result<Vec<T>, _>>()?;
+
+    Ok(Paged {
+        page: options.page.unwrap_or(1),
+        limit: options
+            .limit
+            .unwrap_or(u32::try_from(items.len()).unwrap_or(u32::MAX)),
+        items,
+        total,
+    })
+}
+
+/// Query pairs for `pagination`: `_start`/`_limit` for an offset and limit,
+/// `_page`/`_per_page` for a page number and size
+fn pagination_pairs(pagination: Option<Pagination>) -> [(&'static str, Option<String>); 2] {
+    match pagination {
+        Some(Pagination::OffsetLimit((start, limit))) => [
+            ("_start", Some(start.to_string())),
+            ("_limit", Some(limit.to_string())),
+        ],
+        Some(Pagination::Page((page, per_page))) => [
+            ("_page", Some(page.to_string())),
+            ("_per_page", Some(per_page.to_string())),
+        ],
+        Some(Pagination::None) | None => [("_start", None), ("_limit", None)],
+    }
+}
+
+/// Query pairs for `sort`: `_sort` and `_order`
+fn sort_pairs(sort: Option<SortOptions>) -> [(&'static str, Option<String>); 2] {
+    let Some(sort) = sort else {
+        return [("_sort", None), ("_order", None)];
+    };
+
+    let order = match sort.order {
+        SortOrder::Asc => "asc",
+        SortOrder::Desc => "desc",
+    };
+    [
+        ("_sort", Some(sort.field)),
+        ("_order", Some(order.to_string())),
+    ]
+}
+
+/// `field` as the API spells it
+fn post_sort_field(field: PostSortField) -> &'static str {
+    match field {
+        PostSortField::Id => "id",
+        PostSortField::UserId => "userId",
+        PostSortField::Title => "title",
+    }
+}
+
+/// POST a new todo to `/todos`; an echo without an id is a parse failure
+fn post_todo(title: String, user_id: u64, completed: bool) -> Result<Todo, ComponentError> {
+    let payload = CreateTodoSerde {
+        title,
+        user_id,
+        completed,
+    };
+
+    // An echo without an id is not a created todo, and fails to parse
+    let (todo, _) = post_json::<_, TodoSerde>("/todos", &payload)?;
+    Ok(todo.into())
+}
+
+/// Path of a full-text search of `collection` for `query`, optionally
+/// filtered on `filter`. An empty query is rejected; `hello world` is sent as
+/// `q=hello%20world`.
+fn search_path(
+    collection: &str,
+    query: String,
+    filter: (&str, Option<u64>),
+) -> Result<String, ComponentError> {
+    if query.trim().is_empty() {
+        return Err(ComponentError::InvalidInput(
+            "Search query must not be empty".to_string(),
+        ));
+    }
+
+    let (key, value) = filter;
+    Ok(PathBuilder::resource(collection)
+        .pairs(&[(key, value.map(|v| v.to_string()))])
+        .query("q", &query)
+        .build())
+}
+
+/// Longest title `get-posts` filters on; longer ones match nothing and are
+/// not sent
+const MAX_TITLE_FILTER_CHARS: usize = 300;
+
+/// Most ids sent in one `?id=..&id=..` request, keeping the URL short
+const IDS_PER_REQUEST: usize = 50;
+
+/// Fetch the records of `collection` with any of `ids`, deduplicated and in
+/// batches of `IDS_PER_REQUEST` (`/posts?id=1&id=5`). Ids with no record are
+/// simply absent; no ids means no request.
+fn fetch_by_ids<S: for<'a> Deserialize<'a>>(
+    collection: &str,
+    mut ids: Vec<u64>,
+) -> Result<Vec<S>, ComponentError> {
+    ids.sort_unstable();
+    ids.dedup();
+
+    let mut items = Vec::with_capacity(ids.len());
+    for batch in ids.chunks(IDS_PER_REQUEST) {
+        let path = batch
+            .iter()
+            .fold(PathBuilder::resource(collection), |path, id| {
+                path.query("id", &id.to_string())
+            })
+            .build();
+        items.extend(fetch_json::<Vec<S>>(&path)?);
+    }
+
+    Ok(items)
+}
+
+/// `ordering` of two items, as an ascending comparison, turned to `order`
+fn in_order(ordering: std::cmp::Ordering, order: SortOrder) -> std::cmp::Ordering {
+    match order {
+        SortOrder::Asc => ordering,
+        SortOrder::Desc => ordering.reverse(),
+    }
+}
+
+/// List the `nested` collection under the `parent` record `id`, e.g.
+/// `/users/1/posts`. jsonplaceholder answers an empty array for an unknown
+/// parent, so an empty list is followed by a check that the parent exists,
+/// telling "nothing there" apart from "no such parent". `filter` pairs
+/// that are set go into the query.
+fn fetch_nested<S: for<'a> Deserialize<'a>, T: TryFrom<S>>(
+    parent: &str,
+    id: u64,
+    nested: &str,
+    filter: &[(&str, Option<String>)],
+) -> Result<Vec<T>, ComponentError> {
+    let path = PathBuilder::resource(parent)
+        .id(id)
+        .nested(nested)
+        .pairs(filter)
+        .build();
+    let items = nested_or_missing(fetch_json::<Vec<S>>(&path)?, || {
+        fetch_record::<serde_json::Value>(&PathBuilder::resource(parent).id(id).build(), id)
+            .map(|_| ())
+    })?;
+
+    items
+        .into_iter()
+        .map(|s| T::try_from(s).map_err(|_| ComponentError::Parse))
+        .collect()
+}
+
+/// `items` of a nested list, unless it is empty and `parent_exists` fails:
+/// an empty list under a missing parent is that parent's error, usually
+/// not found
+fn nested_or_missing<S>(
+    items: Vec<S>,
+    parent_exists: impl FnOnce() -> Result<(), ComponentError>,
+) -> Result<Vec<S>, ComponentError> {
+    if items.is_empty() {
+        parent_exists()?;
+    }
+
+    Ok(items)
+}
+
+/// Number of items in the list at `path`; 0 on any failure. The items are
+/// fetched in full since jsonplaceholder has no count endpoint.
+fn count_items(path: &str) -> u64 {
+    fetch_json::<Vec<serde_json::Value>>(path)
+        .map(|v| v.len() as u64)
+        .unwrap_or_default()
+}
+
+/// Path of the `/users` list shared by the user lookups
+fn user_list_path(
+    id: Option<u64>,
+    email: Option<String>,
+    username: Option<String>,
+    pagination: Option<Pagination>,
+    sort: Option<SortOptions>,
+    search: Option<String>,
+) -> String {
+    let [page, size] = pagination_pairs(pagination);
+    let [sort_field, sort_order] = sort_pairs(sort);
+    PathBuilder::resource("users")
+        .pairs(&[
+            ("id", id.map(|v| v.to_string())),
+            ("email", email),
+            ("username", username),
+            page,
+            size,
+            sort_field,
+            sort_order,
+            ("q", search),
+        ])
+        .build()
+}
+
+/// Convert a fetched user; coordinates that are not numbers are a parse
+/// failure
+fn to_user(user: UserSerde) -> Result<User, ComponentError> {
+    User::try_from(user).map_err(|_| ComponentError::Parse)
+}
+
+/// Fetch the user list at `path`
+fn fetch_users(path: &str) -> Result<Vec<User>, ComponentError> {
+    fetch_json::<Vec<UserSerde>>(path)?
+        .into_iter()
+        .map(to_user)
+        .collect()
+}
+
+/// First user of the list at `path`; not found if it is empty
+fn find_user(path: &str) -> Result<User, ComponentError> {
+    fetch_users(path)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| not_found(200, path))
+}
+
+/// Path of the `/albums` list; `title` filters on an exact title
+fn album_list_path(
+    id: Option<u64>,
+    user_id: Option<u64>,
+    title: Option<String>,
+    pagination: Option<Pagination>,
+    sort: Option<SortOptions>,
+    search: Option<String>,
+) -> String {
+    let [page, size] = pagination_pairs(pagination);
+    let [sort_field, sort_order] = sort_pairs(sort);
+    PathBuilder::resource("albums")
+        .pairs(&[
+            ("id", id.map(|v| v.to_string())),
+            ("userId", user_id.map(|v| v.to_string())),
+            ("title", title),
+            page,
+            size,
+            sort_field,
+            sort_order,
+            ("q", search),
+        ])
+        .build()
+}
+
+/// Path of the `/todos` list shared by the todo list functions; `completed`
+/// filters on completion status
+fn todo_list_path(
+    id: Option<u64>,
+    user_id: Option<u64>,
+    completed: Option<bool>,
+    pagination: Option<Pagination>,
+    sort: Option<SortOptions>,
+    search: Option<String>,
+) -> String {
+    let [page, size] = pagination_pairs(pagination);
+    let [sort_field, sort_order] = sort_pairs(sort);
+    PathBuilder::resource("todos")
+        .pairs(&[
+            ("id", id.map(|v| v.to_string())),
+            ("userId", user_id.map(|v| v.to_string())),
+            ("completed", completed.map(|v| v.to_string())),
+            page,
+            size,
+            sort_field,
+            sort_order,
+            ("q", search),
+        ])
+        .build()
+}
+
+/// Fetch the todo list at `path`; an empty list on any failure
+fn fetch_todos(path: &str) -> Vec<Todo> {
+    fetch_json::<Vec<TodoSerde>>(path)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|t| t.into())
+        .collect()
+}
+
+//
+// IMPLEMENTATION OF THE WIT INTERFACE
+//
+
+struct ApiImpl;
+
+impl JsonplaceholderApi for ApiImpl {
+    fn get_all_posts() -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Post> {
+        fetch_json::<Vec<PostSerde>>("/posts")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| p.into())
+            .collect()
+    }
+
+    fn get_posts(
+        user_id: u64,
+        title: Option<String>,
+        pagination: Option<Pagination>,
+        sort: Option<SortOptions>,
+        search: Option<String>,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Post> {
+        if title
+            .as_ref()
+            .is_some_and(|t| t.chars().count() > MAX_TITLE_FILTER_CHARS)
+        {
+            return Vec::new();
+        }
+
+        let [page, size] = pagination_pairs(pagination);
+        let [sort_field, sort_order] = sort_pairs(sort);
+        let path = PathBuilder::resource("posts")
+            .pairs(&[
+                ("userId", Some(user_id.to_string())),
+                ("title", title),
+                page,
+                size,
+                sort_field,
+                sort_order,
+                ("q", search),
+            ])
+            .build();
+
+        fetch_json::<Vec<PostSerde>>(&path)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| p.into())
+            .collect()
+    }
+
+    fn get_posts_paged(
+        user_id: Option<u64>,
+        options: PageOptions,
+    ) -> Result<PaginatedPosts, ComponentError> {
+        let paged = fetch_paged::<PostSerde, _>("posts", ("userId", user_id), options)?;
+        Ok(PaginatedPosts {
+            items: paged.items,
+            total: paged.total,
+            page: paged.page,
+            limit: paged.limit,
+        })
+    }
+
+    fn query_posts(
+        query: PostQuery,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Post> {
+        if query
+            .title
+            .as_ref()
+            .is_some_and(|t| t.chars().count() > MAX_TITLE_FILTER_CHARS)
+        {
+            return Vec::new();
+        }
+
+        let [sort_field, sort_order] = sort_pairs(query.sort.map(|field| SortOptions {
+            field: post_sort_field(field).to_string(),
+            order: query.order.unwrap_or(SortOrder::Asc),
+        }));
+        let path = PathBuilder::resource("posts")
+            .pairs(&[
+                ("userId", query.user_id.map(|v| v.to_string())),
+                ("title", query.title),
+                ("q", query.q),
+                ("_page", query.page.map(|v| v.to_string())),
+                ("_limit", query.limit.map(|v| v.to_string())),
+                sort_field,
+                sort_order,
+            ])
+            .build();
+
+        fetch_json::<Vec<PostSerde>>(&path)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| p.into())
+            .collect()
+    }
+
+    fn get_posts_sorted(
+        user_id: Option<u64>,
+        sort: PostSortField,
+        order: SortOrder,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Post> {
+        let [sort_field, sort_order] = sort_pairs(Some(SortOptions {
+            field: post_sort_field(sort).to_string(),
+            order,
+        }));
+        let path = PathBuilder::resource("posts")
+            .pairs(&[
+                ("userId", user_id.map(|v| v.to_string())),
+                sort_field,
+                sort_order,
+            ])
+            .build();
+
+        fetch_json::<Vec<PostSerde>>(&path)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| p.into())
+            .collect()
+    }
+
+    fn search_posts(
+        query: String,
+        user_id: Option<u64>,
+    ) -> Result<Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Post>, ComponentError> {
+        let path = search_path("posts", query, ("userId", user_id))?;
+        fetch_json::<Vec<PostSerde>>(&path).map(|v| v.into_iter().map(|p| p.into()).collect())
+    }
+
+    fn get_posts_by_ids(
+        ids: Vec<u64>,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Post> {
+        let mut posts: Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Post> =
+            fetch_by_ids::<PostSerde>("posts", ids)
+                .map(|v| v.into_iter().map(|p| p.into()).collect())
+                .unwrap_or_default();
+        posts.sort_by_key(|p| p.id);
+        posts
+    }
+
+    fn get_post(
+        id: u64,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Post, ComponentError> {
+        fetch_record::<PostSerde>(&PathBuilder::resource("posts").id(id).build(), id)
+            .map(|p| p.into())
+    }
+
+    fn create_post(
+        title: String,
+        body: String,
+        user_id: u64,
+    ) -> Result<
+        (
+            exports::jsonplaceholder::api::jsonplaceholder_api::Post,
+            WriteResult,
+        ),
+        ComponentError,
+    > {
+        let payload = PostBuilder::default()
+            .title(title)
+            .body(body)
+            .user_id(user_id)
+            .build()
+            .map_err(ComponentError::InvalidInput)?;
+
+        post_json::<_, PostSerde>("/posts", &payload).map(|(p, result)| (p.into(), result))
+    }
+
+    fn create_posts(
+        posts: Vec<NewPost>,
+    ) -> Vec<Result<exports::jsonplaceholder::api::jsonplaceholder_api::Post, ComponentError>> {
+        // Sequential, in input order; one failure does not affect the rest
+        posts
+            .into_iter()
+            .map(|p| Self::create_post(p.title, p.body, p.user_id).map(|(post, _)| post))
+            .collect()
+    }
+
+    fn update_post(
+        id: u64,
+        title: String,
+        body: String,
+        user_id: u64,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Post, ComponentError> {
+        let payload = PostSerde {
+            id,
+            user_id,
+            title,
+            body,
+        };
+
+        let path = PathBuilder::resource("posts").id(id).build();
+        put_json::<_, serde_json::Value>(&path, &payload)
+            .and_then(|v| echoed_record::<PostSerde>(v, &path))
+            .map(|p| p.into())
+            .map_err(|e| missing_on_write(e, &path))
+    }
+
+    fn patch_post(
+        id: u64,
+        title: Option<String>,
+        body: Option<String>,
+        user_id: Option<u64>,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Post, ComponentError> {
+        if title.is_none() && body.is_none() && user_id.is_none() {
+            return Err(ComponentError::InvalidInput(
+                "Nothing to update".to_string(),
+            ));
+        }
+
+        let payload = PatchPostSerde {
+            title,
+            body,
+            user_id,
+        };
+
+        let path = PathBuilder::resource("posts").id(id).build();
+        patch_json::<_, PostSerde>(&path, &payload)
+            .map(|p| p.into())
+            .map_err(|e| missing_on_write(e, &path))
+    }
+
+    fn delete_post(id: u64) -> Result<(), ComponentError> {
+        delete_resource(&PathBuilder::resource("posts").id(id).build())
+    }
+
+    fn get_post_comments(
+        id: u64,
+    ) -> Result<Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Comment>, ComponentError>
+    {
+        fetch_json::<Vec<CommentSerde>>(
+            &PathBuilder::resource("posts")
+                .id(id)
+                .nested("comments")
+                .build(),
+        )
+        .map(|v| v.into_iter().map(|c| c.into()).collect())
+    }
+
+    fn get_post_with_user(id: u64) -> Result<PostWithUser, ComponentError> {
+        let path = PathBuilder::resource("posts")
+            .id(id)
+            .query("_expand", "user")
+            .build();
+        let expanded = fetch_record::<ExpandedPostSerde>(&path, id)?;
+
+        let post: Post = expanded.post.into();
+        // Servers without `_expand` support send the bare post
+        let user = match expanded.user {
+            Some(user) => to_user(user)?,
+            None => Self::get_user(post.user_id)?,
+        };
+
+        Ok(PostWithUser { post, user })
+    }
+
+    fn get_posts_with_comments(
+        user_id: Option<u64>,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::PostWithComments> {
+        let path = PathBuilder::resource("posts")
+            .pairs(&[
+                ("userId", user_id.map(|v| v.to_string())),
+                ("_embed", Some("comments".to_string())),
+            ])
+            .build();
+
+        fetch_json::<Vec<EmbeddedPostSerde>>(&path)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| {
+                let post: Post = p.post.into();
+                // Servers without `_embed` support send the bare post
+                let comments = match p.comments {
+                    Some(comments) => comments.into_iter().map(|c| c.into()).collect(),
+                    None => Self::get_post_comments(post.id).unwrap_or_default(),
+                };
+                PostWithComments { post, comments }
+            })
+            .collect()
+    }
+
+    fn get_post_with_comments(id: u64) -> Result<PostWithComments, ComponentError> {
+        let post = Self::get_post(id)?;
+        let comments = Self::get_post_comments(id)?;
+
+        Ok(PostWithComments { post, comments })
+    }
+
+    fn get_post_embedded(id: u64, embed: String) -> Result<PostWithEmbedded, ComponentError> {
+        // `embedded` can only hold comments; anything else would always be
+        // none
+        if embed != "comments" {
+            return Err(ComponentError::InvalidInput(format!(
+                "Cannot embed {embed:?} in a post; only \"comments\" is supported"
+            )));
+        }
+
+        let path = PathBuilder::resource("posts")
+            .id(id)
+            .query("_embed", &embed)
+            .build();
+        let post = fetch_record::<EmbeddedPostSerde>(&path, id)?;
+
+        Ok(PostWithEmbedded {
+            post: post.post.into(),
+            embedded: post
+                .comments
+                .map(|v| v.into_iter().map(|c| c.into()).collect()),
+        })
+    }
+
+    fn get_all_comments() -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Comment> {
+        fetch_json::<Vec<CommentSerde>>("/comments")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| c.into())
+            .collect()
+    }
+
+    fn get_comments(
+        id: Option<u64>,
+        post_id: Option<u64>,
+        pagination: Option<Pagination>,
+        sort: Option<SortOptions>,
+        search: Option<String>,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Comment> {
+        let [page, size] = pagination_pairs(pagination);
+        let [sort_field, sort_order] = sort_pairs(sort);
+        let path = PathBuilder::resource("comments")
+            .pairs(&[
+                ("id", id.map(|v| v.to_string())),
+                ("postId", post_id.map(|v| v.to_string())),
+                page,
+                size,
+                sort_field,
+                sort_order,
+                ("q", search),
+            ])
+            .build();
+
+        fetch_json::<Vec<CommentSerde>>(&path)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| c.into())
+            .collect()
+    }
+
+    fn search_comments(
+        query: String,
+        post_id: Option<u64>,
+    ) -> Result<Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Comment>, ComponentError>
+    {
+        let path = search_path("comments", query, ("postId", post_id))?;
+        let mut comments = fetch_json::<Vec<CommentSerde>>(&path)?;
+        comments.truncate(usize::try_from(max_search_results()).unwrap_or(usize::MAX));
+
+        Ok(comments.into_iter().map(|c| c.into()).collect())
+    }
+
+    fn get_comments_sorted(
+        post_id: Option<u64>,
+        sort: CommentSortField,
+        order: SortOrder,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Comment> {
+        let field = match sort {
+            CommentSortField::Id => "id",
+            CommentSortField::PostId => "postId",
+            CommentSortField::Name => "name",
+            CommentSortField::Email => "email",
+        };
+        let [sort_field, sort_order] = sort_pairs(Some(SortOptions {
+            field: field.to_string(),
+            order,
+        }));
+        let path = PathBuilder::resource("comments")
+            .pairs(&[
+                ("postId", post_id.map(|v| v.to_string())),
+                sort_field,
+                sort_order,
+            ])
+            .build();
+
+        let mut comments: Vec<Comment> = fetch_json::<Vec<CommentSerde>>(&path)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| c.into())
+            .collect();
+        // Sorted again locally, as for todos, so the order never depends on
+        // the server honouring `_sort`
+        comments.sort_by(|a, b| {
+            let ordering = match sort {
+                CommentSortField::Id => a.id.cmp(&b.id),
+                CommentSortField::PostId => a.post_id.cmp(&b.post_id),
+                CommentSortField::Name => a.name.cmp(&b.name),
+                CommentSortField::Email => a.email.cmp(&b.email),
+            };
+            in_order(ordering, order)
+        });
+        comments
+    }
+
+    fn get_comments_paged(
+        post_id: Option<u64>,
+        options: PageOptions,
+    ) -> Result<PaginatedComments, ComponentError> {
+        let paged = fetch_paged::<CommentSerde, _>("comments", ("postId", post_id), options)?;
+        Ok(PaginatedComments {
+            items: paged.items,
+            total: paged.total,
+            page: paged.page,
+            limit: paged.limit,
+        })
+    }
+
+    fn create_comment(
+        post_id: u64,
+        name: String,
+        email: String,
+        body: String,
+    ) -> Result<
+        (
+            exports::jsonplaceholder::api::jsonplaceholder_api::Comment,
+            WriteResult,
+        ),
+        ComponentError,
+    > {
+        if !is_valid_email(&email) {
+            return Err(ComponentError::InvalidInput(format!(
+                "Invalid email address: {email:?}"
+            )));
+        }
+
+        let payload = CreateCommentSerde {
+            post_id,
+            name,
+            email,
+            body,
+        };
+
+        post_json::<_, CommentSerde>("/comments", &payload).map(|(c, result)| (c.into(), result))
+    }
+
+    fn update_comment(
+        id: u64,
+        name: Option<String>,
+        email: Option<String>,
+        body: Option<String>,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Comment, ComponentError> {
+        if name.is_none() && email.is_none() && body.is_none() {
+            return Err(ComponentError::InvalidInput(
+                "Nothing to update".to_string(),
+            ));
+        }
+        if let Some(email) = email.as_deref().filter(|e| !is_valid_email(e)) {
+            return Err(ComponentError::InvalidInput(format!(
+                "Invalid email address: {email:?}"
+            )));
+        }
+
+        let payload = PatchCommentSerde { name, email, body };
+
+        let path = PathBuilder::resource("comments").id(id).build();
+        patch_json::<_, serde_json::Value>(&path, &payload)
+            .and_then(|v| echoed_record::<CommentSerde>(v, &path))
+            .map(|c| c.into())
+            .map_err(|e| missing_on_write(e, &path))
+    }
+
+    fn delete_comment(id: u64) -> Result<(), ComponentError> {
+        delete_resource(&PathBuilder::resource("comments").id(id).build())
+    }
+
+    fn get_comment(
+        id: u64,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Comment, ComponentError> {
+        fetch_record::<CommentSerde>(&PathBuilder::resource("comments").id(id).build(), id)
+            .map(|c| c.into())
+    }
+
+    fn get_comment_with_post(id: u64) -> Result<CommentWithPost, ComponentError> {
+        let path = PathBuilder::resource("comments")
+            .id(id)
+            .query("_expand", "post")
+            .build();
+        let expanded = fetch_record::<ExpandedCommentSerde>(&path, id)?;
+
+        let comment: exports::jsonplaceholder::api::jsonplaceholder_api::Comment =
+            expanded.comment.into();
+        // Servers without `_expand` support send the bare comment
+        let post = match expanded.post {
+            Some(post) => post.into(),
+            None => Self::get_post(comment.post_id)?,
+        };
+
+        Ok(CommentWithPost { comment, post })
+    }
+
+    fn get_all_albums() -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Album> {
+        fetch_json::<Vec<AlbumSerde>>("/albums")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|a| a.into())
+            .collect()
+    }
+
+    fn get_albums(
+        id: Option<u64>,
+        user_id: Option<u64>,
+        title: Option<String>,
+        pagination: Option<Pagination>,
+        sort: Option<SortOptions>,
+        search: Option<String>,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Album> {
+        let path = album_list_path(id, user_id, title, pagination, sort, search);
+        fetch_json::<Vec<AlbumSerde>>(&path)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|a| a.into())
+            .collect()
+    }
+
+    fn get_albums_paged(
+        user_id: Option<u64>,
+        options: PageOptions,
+    ) -> Result<PaginatedAlbums, ComponentError> {
+        let paged = fetch_paged::<AlbumSerde, _>("albums", ("userId", user_id), options)?;
+        Ok(PaginatedAlbums {
+            items: paged.items,
+            total: paged.total,
+            page: paged.page,
+            limit: paged.limit,
+        })
+    }
+
+    fn get_album(
+        id: u64,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Album, ComponentError> {
+        fetch_record::<AlbumSerde>(&PathBuilder::resource("albums").id(id).build(), id)
+            .map(|a| a.into())
+    }
+
+    fn create_album(
+        title: String,
+        user_id: u64,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Album, ComponentError> {
+        let payload = CreateAlbumSerde { title, user_id };
+
+        let (mut echoed, _) = post_json::<_, serde_json::Value>("/albums", &payload)?;
+        // Some mock servers echo only the fields they store; keep ours
+        if let Some(map) = echoed.as_object_mut() {
+            map.entry("userId").or_insert(user_id.into());
+        }
+
+        serde_json::from_value::<AlbumSerde>(echoed)
+            .map(|a| a.into())
+            .map_err(|_| ComponentError::Parse)
+    }
+
+    fn rename_album(
+        id: u64,
+        title: String,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Album, ComponentError> {
+        if title.trim().is_empty() {
+            return Err(ComponentError::InvalidInput(
+                "Album title must not be empty".to_string(),
+            ));
+        }
+
+        let path = PathBuilder::resource("albums").id(id).build();
+        patch_json::<_, AlbumSerde>(&path, &PatchAlbumSerde { title })
+            .map(|a| a.into())
+            .map_err(|e| missing_on_write(e, &path))
+    }
+
+    fn delete_album(id: u64) -> Result<(), ComponentError> {
+        delete_resource(&PathBuilder::resource("albums").id(id).build())
+    }
+
+    fn get_album_photos(
+        id: u64,
+    ) -> Result<Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Photo>, ComponentError>
+    {
+        fetch_json::<Vec<PhotoSerde>>(
+            &PathBuilder::resource("albums")
+                .id(id)
+                .nested("photos")
+                .build(),
+        )
+        .map(|v| v.into_iter().map(|p| p.into()).collect())
+    }
+
+    fn get_photos_by_album(
+        album_id: u64,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Photo> {
+        Self::get_album_photos(album_id).unwrap_or_default()
+    }
+
+    fn get_photos_for_user(
+        user_id: u64,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Photo> {
+        let photos = Self::get_user_albums(user_id).and_then(|albums| {
+            albums.iter().try_fold(Vec::new(), |mut photos, album| {
+                photos.extend(Self::get_album_photos(album.id)?);
+                Ok(photos)
+            })
+        });
+        photos.unwrap_or_default()
+    }
+
+    fn get_albums_with_photos(
+        user_id: Option<u64>,
+        photo_limit: Option<u32>,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::AlbumWithPhotos> {
+        let path = PathBuilder::resource("albums")
+            .pairs(&[("userId", user_id.map(|v| v.to_string()))])
+            .query("_embed", "photos")
+            .build();
+        let limit = photo_limit.map_or(usize::MAX, |l| usize::try_from(l).unwrap_or(usize::MAX));
+
+        fetch_json::<Vec<EmbeddedAlbumSerde>>(&path)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|a| {
+                // Keep the lowest ids so truncation does not depend on server order
+                let mut photos = a.photos;
+                photos.sort_by_key(|p| p.id);
+                photos.truncate(limit);
+                AlbumWithPhotos {
+                    album: a.album.into(),
+                    photos: photos.into_iter().map(|p| p.into()).collect(),
+                }
+            })
+            .collect()
+    }
+
+    fn get_album_with_photos(id: u64) -> Result<AlbumWithPhotos, ComponentError> {
+        let album = Self::get_album(id)?;
+        let photos = Self::get_album_photos(id)?;
+
+        Ok(AlbumWithPhotos { album, photos })
+    }
+
+    fn get_all_photos() -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Photo> {
+        fetch_json::<Vec<PhotoSerde>>("/photos")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| p.into())
+            .collect()
+    }
+
+    fn get_photos(
+        id: Option<u64>,
+        album_id: Option<u64>,
+        title: Option<String>,
+        title_contains: bool,
+        pagination: Option<Pagination>,
+        sort: Option<SortOptions>,
+        search: Option<String>,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Photo> {
+        let [page, size] = pagination_pairs(pagination);
+        let [sort_field, sort_order] = sort_pairs(sort);
+        let path = PathBuilder::resource("photos")
+            .pairs(&[
+                ("id", id.map(|v| v.to_string())),
+                ("albumId", album_id.map(|v| v.to_string())),
+                ("title", title.clone()),
+                page,
+                size,
+                sort_field,
+                sort_order,
+                ("q", search),
+            ])
+            .build();
+
+        let photos: Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Photo> =
+            fetch_json::<Vec<PhotoSerde>>(&path)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|p| p.into())
+                .collect();
+
+        // No exact match: fall back to matching part of the title among the
+        // album's photos
+        match (title, album_id) {
+            (Some(title), Some(album_id)) if title_contains && photos.is_empty() => {
+                Self::get_photos_by_album(album_id)
+                    .into_iter()
+                    .filter(|p| p.title.contains(&title))
+                    .collect()
+            }
+            _ => photos,
+        }
+    }
+
+    fn get_photos_paged(
+        album_id: Option<u64>,
+        options: PageOptions,
+    ) -> Result<PaginatedPhotos, ComponentError> {
+        let paged = fetch_paged::<PhotoSerde, _>("photos", ("albumId", album_id), options)?;
+        Ok(PaginatedPhotos {
+            items: paged.items,
+            total: paged.total,
+            page: paged.page,
+            limit: paged.limit,
+        })
+    }
+
+    fn get_photos_page(
+        album_id: Option<u64>,
+        page: u32,
+        limit: u32,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Photo> {
+        // Only the requested slice of the 5000 photos is transferred and parsed
+        let options = PageOptions {
+            page: Some(page),
+            limit: Some(limit),
+            start: None,
+            end: None,
+        };
+        fetch_paged::<PhotoSerde, _>("photos", ("albumId", album_id), options)
+            .map(|paged| paged.items)
+            .unwrap_or_default()
+    }
+
+    fn get_photo(
+        id: u64,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Photo, ComponentError> {
+        fetch_record::<PhotoSerde>(&PathBuilder::resource("photos").id(id).build(), id)
+            .map(|p| p.into())
+    }
+
+    fn create_photo(
+        album_id: u64,
+        title: String,
+        url: String,
+        thumbnail_url: String,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Photo, ComponentError> {
+        check_http_url("url", &url)?;
+        check_http_url("thumbnail-url", &thumbnail_url)?;
+
+        let payload = CreatePhotoSerde {
+            album_id,
+            title,
+            url,
+            thumbnail_url,
+        };
+
+        post_json::<_, PhotoSerde>("/photos", &payload).map(|(p, _)| p.into())
+    }
+
+    fn update_photo(
+        id: u64,
+        title: Option<String>,
+        url: Option<String>,
+        thumbnail_url: Option<String>,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Photo, ComponentError> {
+        if title.is_none() && url.is_none() && thumbnail_url.is_none() {
+            return Err(ComponentError::InvalidInput(
+                "Nothing to update".to_string(),
+            ));
+        }
+        if let Some(url) = &url {
+            check_http_url("url", url)?;
+        }
+        if let Some(thumbnail_url) = &thumbnail_url {
+            check_http_url("thumbnail-url", thumbnail_url)?;
+        }
+
+        let payload = PatchPhotoSerde {
+            title,
+            url,
+            thumbnail_url,
+        };
+
+        let path = PathBuilder::resource("photos").id(id).build();
+        patch_json::<_, PhotoSerde>(&path, &payload)
+            .map(|p| p.into())
+            .map_err(|e| missing_on_write(e, &path))
+    }
+
+    fn delete_photo(id: u64) -> Result<(), ComponentError> {
+        // jsonplaceholder ids start at 1
+        if id == 0 {
+            return Err(ComponentError::InvalidInput(
+                "Photo id must be at least 1".to_string(),
+            ));
+        }
+
+        delete_resource(&PathBuilder::resource("photos").id(id).build())
+    }
+
+    fn get_all_todos() -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Todo> {
+        fetch_json::<Vec<TodoSerde>>("/todos")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|t| t.into())
+            .collect()
+    }
+
+    fn get_todos(
+        id: Option<u64>,
+        user_id: Option<u64>,
+        pagination: Option<Pagination>,
+        sort: Option<SortOptions>,
+        search: Option<String>,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Todo> {
+        let path = todo_list_path(id, user_id, None, pagination, sort, search);
+        fetch_todos(&path)
+    }
+
+    fn get_todos_paged(
+        user_id: Option<u64>,
+        options: PageOptions,
+    ) -> Result<PaginatedTodos, ComponentError> {
+        let paged = fetch_paged::<TodoSerde, _>("todos", ("userId", user_id), options)?;
+        Ok(PaginatedTodos {
+            items: paged.items,
+            total: paged.total,
+            page: paged.page,
+            limit: paged.limit,
+        })
+    }
+
+    fn get_todos_sorted(
+        user_id: Option<u64>,
+        sort: TodoSortField,
+        order: SortOrder,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Todo> {
+        let field = match sort {
+            TodoSortField::Id => "id",
+            TodoSortField::UserId => "userId",
+            TodoSortField::Title => "title",
+            TodoSortField::Completed => "completed",
+        };
+        let path = todo_list_path(
+            None,
+            user_id,
+            None,
+            None,
+            Some(SortOptions {
+                field: field.to_string(),
+                order,
+            }),
+            None,
+        );
+
+        // Older json-server versions ignore `_sort`; a stable sort keeps the
+        // server's order when it did sort and sorts otherwise
+        let mut todos = fetch_todos(&path);
+        todos.sort_by(|a, b| {
+            let ordering = match sort {
+                TodoSortField::Id => a.id.cmp(&b.id),
+                TodoSortField::UserId => a.user_id.cmp(&b.user_id),
+                TodoSortField::Title => a.title.cmp(&b.title),
+                TodoSortField::Completed => a.completed.cmp(&b.completed),
+            };
+            in_order(ordering, order)
+        });
+        todos
+    }
+
+    fn get_todos_completed(
+        user_id: Option<u64>,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Todo> {
+        let path = todo_list_path(None, user_id, Some(true), None, None, None);
+        fetch_todos(&path)
+    }
+
+    fn get_todos_pending(
+        user_id: Option<u64>,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Todo> {
+        let path = todo_list_path(None, user_id, Some(false), None, None, None);
+        fetch_todos(&path)
+    }
+
+    fn get_todo(
+        id: u64,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Todo, ComponentError> {
+        fetch_record::<TodoSerde>(&PathBuilder::resource("todos").id(id).build(), id)
+            .map(|t| t.into())
+    }
+
+    fn set_todo_completed(
+        id: u64,
+        completed: bool,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Todo, ComponentError> {
+        let path = PathBuilder::resource("todos").id(id).build();
+        patch_json::<_, TodoSerde>(&path, &PatchTodoSerde { completed })
+            .map(|t| t.into())
+            .map_err(|e| missing_on_write(e, &path))
+    }
+
+    fn update_todo_completed(
+        id: u64,
+        completed: bool,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Todo, ComponentError> {
+        Self::set_todo_completed(id, completed)
+    }
+
+    fn delete_todo(id: u64) -> Result<(), ComponentError> {
+        delete_resource(&PathBuilder::resource("todos").id(id).build())
+    }
+
+    fn create_todo(
+        title: String,
+        user_id: u64,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Todo, ComponentError> {
+        post_todo(title, user_id, false)
+    }
+
+    fn create_todo_with_completed(
+        title: String,
+        user_id: u64,
+        completed: bool,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::Todo, ComponentError> {
+        post_todo(title, user_id, completed)
+    }
+
+    fn get_all_users() -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::User> {
+        fetch_users("/users").unwrap_or_default()
+    }
+
+    fn get_users(
+        id: Option<u64>,
+        email: Option<String>,
+        pagination: Option<Pagination>,
+        sort: Option<SortOptions>,
+        search: Option<String>,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::User> {
+        let path = user_list_path(id, email, None, pagination, sort, search);
+        fetch_users(&path).unwrap_or_default()
+    }
+
+    fn get_users_paged(options: PageOptions) -> Result<PaginatedUsers, ComponentError> {
+        let paged = fetch_paged::<UserSerde, _>("users", ("id", None), options)?;
+        Ok(PaginatedUsers {
+            items: paged.items,
+            total: paged.total,
+            page: paged.page,
+            limit: paged.limit,
+        })
+    }
+
+    fn get_users_with_posts(
+        id: Option<u64>,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::UserWithPosts> {
+        let path = PathBuilder::resource("users")
+            .pairs(&[("id", id.map(|v| v.to_string()))])
+            .query("_embed", "posts")
+            .build();
+
+        // A user that does not convert fails the whole list, like get-users
+        fetch_json::<Vec<EmbeddedUserSerde>>(&path)
+            .and_then(|users| {
+                users
+                    .into_iter()
+                    .map(|u| {
+                        Ok(UserWithPosts {
+                            user: to_user(u.user)?,
+                            posts: u.posts.into_iter().map(|p| p.into()).collect(),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn search_users(
+        query: String,
+    ) -> Result<Vec<exports::jsonplaceholder::api::jsonplaceholder_api::User>, ComponentError> {
+        // `@` and `+` in email-like queries are percent-encoded with the rest
+        fetch_users(&search_path("users", query, ("id", None))?)
+    }
+
+    fn get_user_by_username(
+        username: String,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::User, ComponentError> {
+        find_user(&user_list_path(
+            None,
+            None,
+            Some(username),
+            None,
+            None,
+            None,
+        ))
+    }
+
+    fn get_user_by_email(
+        email: String,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::User, ComponentError> {
+        find_user(&user_list_path(None, Some(email), None, None, None, None))
+    }
+
+    fn get_users_by_ids(
+        ids: Vec<u64>,
+    ) -> Vec<exports::jsonplaceholder::api::jsonplaceholder_api::User> {
+        let mut users: Vec<exports::jsonplaceholder::api::jsonplaceholder_api::User> =
+            fetch_by_ids::<UserSerde>("users", ids)
+                .and_then(|v| v.into_iter().map(to_user).collect())
+                .unwrap_or_default();
+        users.sort_by_key(|u| u.id);
+        users
+    }
+
+    fn get_user(
+        id: u64,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::User, ComponentError> {
+        fetch_record::<UserSerde>(&PathBuilder::resource("users").id(id).build(), id)
+            .and_then(to_user)
+    }
+
+    fn create_user(
+        user: NewUser,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::User, ComponentError> {
+        if !is_valid_email(&user.email) {
+            return Err(ComponentError::InvalidInput(format!(
+                "Invalid email address: {:?}",
+                user.email
+            )));
+        }
+
+        let payload = CreateUserSerde {
+            name: user.name,
+            username: user.username,
+            email: user.email,
+            phone: user.phone,
+            website: user.website,
+            company: user.company.into(),
+            address: user.address.into(),
+        };
+
+        // An echo without an id is not a created user, and fails to parse
+        let (user, _) = post_json::<_, UserSerde>("/users", &payload)?;
+        to_user(user)
+    }
+
+    fn update_user(
+        id: u64,
+        patch: UserPatch,
+    ) -> Result<exports::jsonplaceholder::api::jsonplaceholder_api::User, ComponentError> {
+        if let Some(email) = patch.email.as_deref().filter(|e| !is_valid_email(e)) {
+            return Err(ComponentError::InvalidInput(format!(
+                "Invalid email address: {email:?}"
+            )));
+        }
+
+        // Nested patches setting nothing are dropped, so this also catches
+        // `company: some({})`
+        let payload = PatchUserSerde::from(patch);
+        if payload.is_empty() {
+            return Err(ComponentError::InvalidInput(
+                "Nothing to update".to_string(),
+            ));
+        }
+
+        let path = PathBuilder::resource("users").id(id).build();
+        patch_json::<_, UserSerde>(&path, &payload)
+            .and_then(to_user)
+            .map_err(|e| missing_on_write(e, &path))
+    }
+
+    fn delete_user(id: u64) -> Result<(), ComponentError> {
+        delete_resource(&PathBuilder::resource("users").id(id).build())
+    }
+
+    fn get_user_posts(
+        user_id: u64,
+    ) -> Result<Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Post>, ComponentError> {
+        fetch_nested::<PostSerde, _>("users", user_id, "posts", &[])
+    }
+
+    fn get_user_albums(
+        user_id: u64,
+    ) -> Result<Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Album>, ComponentError>
+    {
+        fetch_nested::<AlbumSerde, _>("users", user_id, "albums", &[])
+    }
+
+    fn get_user_todos(
+        user_id: u64,
+        completed: Option<bool>,
+    ) -> Result<Vec<exports::jsonplaceholder::api::jsonplaceholder_api::Todo>, ComponentError> {
+        fetch_nested::<TodoSerde, _>(
+            "users",
+            user_id,
+            "todos",
+            &[("completed", completed.map(|v| v.to_string()))],
+        )
+    }
+
+    fn get_user_full_profile(id: u64) -> Result<UserProfile, ComponentError> {
+        let user = Self::get_user(id)?;
+        // The user exists; a failed listing is reported as empty
+        let posts = Self::get_user_posts(id).unwrap_or_default();
+        let albums = Self::get_user_albums(id).unwrap_or_default();
+        let todos = Self::get_user_todos(id, None).unwrap_or_default();
+
+        Ok(UserProfile {
+            user,
+            posts,
+            albums,
+            todos,
+        })
+    }
+
+    fn count_posts_for_user(user_id: u64) -> u64 {
+        count_items(
+            &PathBuilder::resource("users")
+                .id(user_id)
+                .nested("posts")
+                .build(),
+        )
+    }
+
+    fn count_comments_for_post(post_id: u64) -> u64 {
+        count_items(
+            &PathBuilder::resource("posts")
+                .id(post_id)
+                .nested("comments")
+                .build(),
+        )
+    }
+
+    fn count_todos_for_user(user_id: u64) -> u64 {
+        count_items(
+            &PathBuilder::resource("users")
+                .id(user_id)
+                .nested("todos")
+                .build(),
+        )
+    }
+
+    fn exists_post(id: u64) -> bool {
+        resource_exists(&PathBuilder::resource("posts").id(id).build())
+    }
+
+    fn exists_user(id: u64) -> bool {
+        resource_exists(&PathBuilder::resource("users").id(id).build())
+    }
+
+    fn exists_todo(id: u64) -> bool {
+        resource_exists(&PathBuilder::resource("todos").id(id).build())
+    }
+
+    fn set_base_url(authority: String, scheme: Option<String>) -> Result<(), ComponentError> {
+        let https = match scheme.as_deref() {
+            None | Some("https") => true,
+            Some("http") => false,
+            Some(other) => {
+                return Err(ComponentError::InvalidInput(format!(
+                    "Unsupported scheme: {other:?}"
+                )))
+            }
+        };
+        if !is_valid_authority(&authority) {
+            return Err(ComponentError::InvalidInput(format!(
+                "Invalid authority: {authority:?}"
+            )));
+        }
+
+        *BASE_URL.lock().unwrap_or_else(|e| e.into_inner()) = Some(BaseUrl { authority, https });
+        // Cached bodies came from the previous server
+        cache().clear();
+        Ok(())
+    }
+
+    fn set_user_agent(value: String) -> Result<(), ComponentError> {
+        if value.trim().is_empty() || value.chars().any(|c| c.is_control()) {
+            return Err(ComponentError::InvalidInput(format!(
+                "Invalid user agent: {value:?}"
+            )));
+        }
+
+        *USER_AGENT.lock().unwrap_or_else(|e| e.into_inner()) = Some(value);
+        Ok(())
+    }
+
+    fn set_max_retries(retries: u8) {
+        *MAX_RETRIES.lock().unwrap_or_else(|e| e.into_inner()) = retries;
+    }
+
+    fn set_request_timeout_ms(ms: u64) {
+        *REQUEST_TIMEOUT_MS.lock().unwrap_or_else(|e| e.into_inner()) = ms;
+    }
+
+    fn set_rate_limit_retries(retries: u8) {
+        *RATE_LIMIT_RETRIES.lock().unwrap_or_else(|e| e.into_inner()) = retries;
+    }
+
+    fn set_max_retry_after_secs(secs: u64) {
+        *MAX_RETRY_AFTER_SECS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = secs;
+    }
+
+    fn clear_cache() {
+        cache().clear();
+    }
+
+    fn invalidate_path(path: String) {
+        invalidate_path(&path);
+    }
+
+    fn set_max_response_bytes(limit: u64) {
+        *MAX_RESPONSE_BYTES.lock().unwrap_or_else(|e| e.into_inner()) = limit;
+    }
+
+    fn set_max_search_results(max: u32) {
+        *MAX_SEARCH_RESULTS.lock().unwrap_or_else(|e| e.into_inner()) = max;
+    }
+}
+
+__export_jsonplaceholder_impl!(ApiImpl);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exports::jsonplaceholder::api::jsonplaceholder_api::{
+        AddressPatch, Album, CompanyPatch, GeoPatch,
+    };
+    use std::cell::{Cell, RefCell};
+
+    #[test]
+    fn set_base_url_rejects_malformed_authorities() {
+        for authority in [
+            "",
+            "localhost:3000 ",
+            " localhost",
+            "a?b",
+            "user@host#x",
+            "host/path",
+            "host\tname",
+            "host\u{7f}",
+        ] {
+            assert!(
+                matches!(
+                    ApiImpl::set_base_url(authority.to_string(), None),
+                    Err(ComponentError::InvalidInput(_))
+                ),
+                "{authority:?} accepted"
+            );
+        }
+    }
+
+    fn empty_user_patch() -> UserPatch {
+        UserPatch {
+            name: None,
+            username: None,
+            email: None,
+            phone: None,
+            website: None,
+            company: None,
+            address: None,
+        }
+    }
+
+    #[test]
+    fn update_user_rejects_patches_setting_nothing_nested() {
+        let company = UserPatch {
+            company: Some(CompanyPatch {
+                name: None,
+                catch_phrase: None,
+                bs: None,
+            }),
+            ..empty_user_patch()
+        };
+        let geo = UserPatch {
+            address: Some(AddressPatch {
+                street: None,
+                suite: None,
+                city: None,
+                zipcode: None,
+                geo: Some(GeoPatch {
+                    lat: None,
+                    lng: None,
+                }),
+            }),
+            ..empty_user_patch()
+        };
+
+        for patch in [empty_user_patch(), company, geo] {
+            assert_eq!(
+                ApiImpl::update_user(1, patch),
+                Err(ComponentError::InvalidInput(
+                    "Nothing to update".to_string()
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn user_patch_sends_only_the_nested_fields_set() {
+        let patch = UserPatch {
+            address: Some(AddressPatch {
+                street: None,
+                suite: None,
+                city: Some("Gwenborough".to_string()),
+                zipcode: None,
+                geo: Some(GeoPatch {
+                    lat: None,
+                    lng: None,
+                }),
+            }),
+            company: Some(CompanyPatch {
+                name: None,
+                catch_phrase: None,
+                bs: None,
+            }),
+            ..empty_user_patch()
+        };
+
+        assert_eq!(
+            serde_json::to_value(PatchUserSerde::from(patch)).unwrap(),
+            serde_json::json!({"address": {"city": "Gwenborough"}})
+        );
+    }
+
+    fn page_options(
+        page: Option<u32>,
+        limit: Option<u32>,
+        start: Option<u32>,
+        end: Option<u32>,
+    ) -> PageOptions {
+        PageOptions {
+            page,
+            limit,
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn range_options_become_start_and_end() {
+        let options = page_options(None, None, Some(20), Some(40));
+        assert_eq!(
+            paged_path("comments", ("postId", Some(7)), &options),
+            Ok("/comments?postId=7&_start=20&_end=40".to_string())
+        );
+    }
+
+    #[test]
+    fn page_options_become_page_and_limit() {
+        let options = page_options(Some(2), Some(10), None, None);
+        assert_eq!(
+            paged_path("posts", ("userId", Some(3)), &options),
+            Ok("/posts?userId=3&_page=2&_limit=10".to_string())
+        );
+    }
+
+    #[test]
+    fn no_page_options_leave_the_path_unpaged() {
+        let options = page_options(None, None, None, None);
+        assert_eq!(
+            paged_path("posts", ("userId", None), &options),
+            Ok("/posts".to_string())
+        );
+    }
+
+    #[test]
+    fn page_options_apply_alike_to_every_paged_endpoint() {
+        // Collection and filter of each `*-paged` function
+        let cases = [
+            (
+                "posts",
+                ("userId", Some(1)),
+                "/posts?userId=1&_page=3&_limit=5",
+            ),
+            (
+                "comments",
+                ("postId", Some(2)),
+                "/comments?postId=2&_page=3&_limit=5",
+            ),
+            (
+                "albums",
+                ("userId", Some(3)),
+                "/albums?userId=3&_page=3&_limit=5",
+            ),
+            (
+                "photos",
+                ("albumId", Some(4)),
+                "/photos?albumId=4&_page=3&_limit=5",
+            ),
+            (
+                "todos",
+                ("userId", Some(5)),
+                "/todos?userId=5&_page=3&_limit=5",
+            ),
+            ("users", ("id", None), "/users?_page=3&_limit=5"),
+        ];
+
+        let options = page_options(Some(3), Some(5), None, None);
+        for (collection, filter, expected) in cases {
+            assert_eq!(
+                paged_path(collection, filter, &options),
+                Ok(expected.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn page_and_range_options_cannot_be_mixed() {
+        let options = page_options(Some(2), None, Some(20), None);
+        assert!(matches!(
+            paged_path("comments", ("postId", Some(7)), &options),
+            Err(ComponentError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn empty_responses_by_status_and_body() {
+        let cases: [(u16, &[u8], Result<(), ComponentError>); 6] = [
+            (200, b"", Ok(())),
+            (204, b" \r\n", Ok(())),
+            (200, b"{}", Ok(())),
+            (204, b"{ }", Ok(())),
+            (200, br#"{"a":1}"#, Err(ComponentError::Parse)),
+            (500, b"", Err(ComponentError::ServerError(500))),
+        ];
+
+        for (status, body, expected) in cases {
+            assert_eq!(
+                expect_empty_response("/comments/1", status, body),
+                expected,
+                "{status} {:?}",
+                String::from_utf8_lossy(body)
+            );
+        }
+    }
+
+    #[test]
+    fn unprocessable_write_carries_the_json_message() {
+        let body = br#"{"message": "title is required"}"#;
+        assert_eq!(
+            write_error(422, "/posts", body),
+            ComponentError::ValidationError(ValidationError {
+                status: 422,
+                detail: "title is required".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn bad_request_carries_the_plain_text_body() {
+        assert_eq!(
+            write_error(400, "/posts", b"Bad Request: body is not JSON\n"),
+            ComponentError::ValidationError(ValidationError {
+                status: 400,
+                detail: "Bad Request: body is not JSON".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn other_write_failures_are_not_validation_errors() {
+        assert!(matches!(
+            write_error(404, "/posts/1", b"{}"),
+            ComponentError::NotFound(_)
+        ));
+        assert_eq!(
+            write_error(503, "/posts", b"unavailable"),
+            ComponentError::ServerError(503)
+        );
+    }
+
+    #[test]
+    fn error_detail_falls_back_from_message_to_error_to_text() {
+        assert_eq!(
+            error_detail(br#"{"error": "duplicate id"}"#),
+            "duplicate id"
+        );
+        assert_eq!(error_detail(br#"{"code": 7}"#), r#"{"code": 7}"#);
+        assert_eq!(error_detail(b"  oops  "), "oops");
+    }
+
+    #[test]
+    fn search_query_is_percent_encoded() {
+        assert_eq!(
+            search_path("posts", "hello world".to_string(), ("userId", Some(1))),
+            Ok("/posts?userId=1&q=hello%20world".to_string())
+        );
+        assert_eq!(
+            search_path("posts", "a&b=ü".to_string(), ("userId", None)),
+            Ok("/posts?q=a%26b%3D%C3%BC".to_string())
+        );
+    }
+
+    #[test]
+    fn blank_search_query_is_rejected() {
+        for query in ["", "   "] {
+            assert!(matches!(
+                search_path("posts", query.to_string(), ("userId", None)),
+                Err(ComponentError::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn email_filter_encodes_plus_and_at() {
+        assert_eq!(
+            user_list_path(
+                Some(1),
+                Some("foo+bar@example.com".to_string()),
+                None,
+                None,
+                None,
+                None
+            ),
+            "/users?id=1&email=foo%2Bbar%40example.com"
+        );
+    }
+
+    #[test]
+    fn ampersand_in_album_title_keeps_the_other_filters() {
+        assert_eq!(
+            album_list_path(
+                Some(1),
+                Some(2),
+                Some("rock & roll".to_string()),
+                Some(Pagination::OffsetLimit((0, 5))),
+                None,
+                None
+            ),
+            "/albums?id=1&userId=2&title=rock%20%26%20roll&_start=0&_limit=5"
+        );
+    }
+
+    #[test]
+    fn failed_write_to_an_unknown_id_names_the_id() {
+        let path = PathBuilder::resource("todos").id(7).build();
+        // jsonplaceholder answers 500 to writes to unknown ids, a real
+        // json-server 404
+        for e in [
+            missing_on_write(ComponentError::ServerError(500), &path),
+            missing_on_write(status_error(404, &path), &path),
+        ] {
+            let ComponentError::NotFound(e) = e else {
+                panic!("{e:?} is not not-found");
+            };
+            assert_eq!(e.path, "/todos/7");
+            assert!(e.message.contains("/todos/7"), "{}", e.message);
+        }
+    }
+
+    #[test]
+    fn rename_album_rejects_an_empty_title_before_sending() {
+        for title in ["", "  "] {
+            assert!(matches!(
+                ApiImpl::rename_album(1, title.to_string()),
+                Err(ComponentError::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn rename_album_sends_only_the_title_and_reads_the_echo() {
+        let payload = PatchAlbumSerde {
+            title: "Renamed".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&payload).unwrap(),
+            serde_json::json!({"title": "Renamed"})
+        );
+
+        let echo: AlbumSerde =
+            serde_json::from_str(r#"{"userId": 1, "id": 2, "title": "Renamed"}"#).unwrap();
+        assert_eq!(
+            Album::from(echo),
+            Album {
+                id: 2,
+                user_id: 1,
+                title: "Renamed".to_string(),
+            }
+        );
+    }
+
+    /// Output stream granting `budget` bytes per `check-write`, except every
+    /// third call, which asks the writer to wait
+    struct FakeSink {
+        budget: u64,
+        checks: Cell<u32>,
+        written: RefCell<Vec<Vec<u8>>>,
+        flushes: Cell<u32>,
+        waits: Cell<u32>,
+    }
+
+    impl FakeSink {
+        fn new(budget: u64) -> Self {
+            FakeSink {
+                budget,
+                checks: Cell::new(0),
+                written: RefCell::new(Vec::new()),
+                flushes: Cell::new(0),
+                waits: Cell::new(0),
+            }
+        }
+    }
+
+    impl OutputSink for FakeSink {
+        fn check_write(&self) -> Result<u64, ComponentError> {
+            self.checks.set(self.checks.get() + 1);
+            Ok(if self.checks.get().is_multiple_of(3) {
+                0
+            } else {
+                self.budget
+            })
+        }
+
+        fn write(&self, chunk: &[u8]) -> Result<(), ComponentError> {
+            assert!(chunk.len() as u64 <= self.budget, "wrote over budget");
+            self.written.borrow_mut().push(chunk.to_vec());
+            Ok(())
+        }
+
+        fn flush(&self) -> Result<(), ComponentError> {
+            self.flushes.set(self.flushes.get() + 1);
+            Ok(())
+        }
+
+        fn wait(&self) {
+            self.waits.set(self.waits.get() + 1);
+        }
+    }
+
+    #[test]
+    fn large_payload_is_written_in_budget_sized_chunks() {
+        let payload: Vec<u8> = (0..70 * 1024).map(|i| (i % 251) as u8).collect();
+        let sink = FakeSink::new(4096);
+
+        write_all(&sink, &payload).unwrap();
+
+        let written = sink.written.borrow();
+        assert!(written.len() >= payload.len() / 4096);
+        assert_eq!(written.concat(), payload);
+        assert!(sink.waits.get() > 1, "never waited for budget");
+        assert_eq!(sink.flushes.get(), 1);
+    }
+
+    #[test]
+    fn empty_payload_writes_nothing_but_still_flushes() {
+        let sink = FakeSink::new(4096);
+
+        write_all(&sink, &[]).unwrap();
+
+        assert!(sink.written.borrow().is_empty());
+        assert_eq!(sink.flushes.get(), 1);
+        assert_eq!(sink.waits.get(), 1);
+    }
+
+    /// Serves `body` in reads no larger than asked, recording each request
+    struct FakeSource {
+        body: Vec<u8>,
+        served: Cell<usize>,
+        largest_read: Cell<u64>,
+    }
+
+    impl FakeSource {
+        fn new(body: &[u8]) -> Self {
+            FakeSource {
+                body: body.to_vec(),
+                served: Cell::new(0),
+                largest_read: Cell::new(0),
+            }
+        }
+    }
+
+    impl InputSource for FakeSource {
+        fn read(&self, len: u64) -> Result<Option<Vec<u8>>, ComponentError> {
+            self.largest_read.set(self.largest_read.get().max(len));
+            let start = self.served.get();
+            if start == self.body.len() {
+                return Ok(None);
+            }
+            let end = self.body.len().min(start + len as usize);
+            self.served.set(end);
+            Ok(Some(self.body[start..end].to_vec()))
+        }
+    }
+
+    // Shaped like jsonplaceholder's answer to
+    // `GET /photos?albumId=1&_page=1&_limit=50`
+    const PHOTO_PAGE: &str = include_str!("../tests/fixtures/photos_album_1_limit_50.json");
+
+    #[test]
+    fn photo_page_parses_within_a_small_read_buffer() {
+        const BOUND: u64 = 256 * 1024;
+        let source = FakeSource::new(PHOTO_PAGE.as_bytes());
+
+        let photos: Vec<PhotoSerde> = parse_bounded(&source, BOUND).unwrap();
+
+        assert_eq!(photos.len(), 50);
+        assert!(photos.iter().all(|p| p.album_id == 1));
+        assert_eq!(source.served.get(), PHOTO_PAGE.len());
+        // Streamed through the reader's buffer, never the whole body at once
+        assert!(source.largest_read.get() <= 8 * 1024);
+        assert!((source.served.get() as u64) < BOUND);
+    }
+
+    #[test]
+    fn photo_page_over_the_limit_is_too_large() {
+        let limit = PHOTO_PAGE.len() as u64 - 1;
+
+        assert_eq!(
+            parse_bounded::<Vec<PhotoSerde>>(&FakeSource::new(PHOTO_PAGE.as_bytes()), limit),
+            Err(ComponentError::ResponseTooLarge(limit))
+        );
+        assert_eq!(
+            read_all(&FakeSource::new(PHOTO_PAGE.as_bytes()), limit),
+            Err(ComponentError::ResponseTooLarge(limit))
+        );
+        assert_eq!(
+            read_all(&FakeSource::new(PHOTO_PAGE.as_bytes()), limit + 1).unwrap(),
+            PHOTO_PAGE.as_bytes()
+        );
+    }
+
+    #[test]
+    fn update_post_sends_the_full_post_and_reads_the_echo() {
+        let payload = PostSerde {
+            id: 1,
+            user_id: 1,
+            title: "t".to_string(),
+            body: "b".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&payload).unwrap(),
+            serde_json::json!({"id": 1, "userId": 1, "title": "t", "body": "b"})
+        );
+
+        // jsonplaceholder's answer to `PUT /posts/1`
+        let echo = serde_json::json!({"id": 1, "title": "t", "body": "b", "userId": 1});
+        let post: PostSerde = echoed_record(echo, "/posts/1").unwrap();
+        assert_eq!(Post::from(post), Post::from(payload));
+    }
+
+    #[test]
+    fn update_post_to_an_unknown_id_is_not_found() {
+        let path = "/posts/101";
+        assert!(matches!(
+            echoed_record::<PostSerde>(serde_json::json!({}), path),
+            Err(ComponentError::NotFound(_))
+        ));
+        for status in [404, 500] {
+            assert!(matches!(
+                missing_on_write(write_error(status, path, b"{}"), path),
+                ComponentError::NotFound(NotFoundError { status: s, .. }) if s == status
+            ));
+        }
+    }
+
+    #[test]
+    fn user_albums_fixture_converts_to_albums() {
+        // Head of jsonplaceholder's answer to `GET /users/1/albums`
+        let fixture = r#"[
+            {"userId": 1, "id": 1, "title": "quidem molestiae enim"},
+            {"userId": 1, "id": 2, "title": "sunt qui excepturi placeat culpa"}
+        ]"#;
+        let albums: Vec<Album> = serde_json::from_str::<Vec<AlbumSerde>>(fixture)
+            .unwrap()
+            .into_iter()
+            .map(Album::from)
+            .collect();
+
+        assert_eq!(albums.len(), 2);
+        assert!(albums.iter().all(|a| a.user_id == 1));
+    }
+
+    #[test]
+    fn empty_list_under_a_missing_parent_is_not_found() {
+        // `get-user-albums(0)`: `/users/0/albums` is `[]` and `/users/0` is 404
+        let albums = nested_or_missing(Vec::<AlbumSerde>::new(), || {
+            Err(status_error(404, "/users/0"))
+        });
+
+        assert_eq!(
+            albums,
+            Err(ComponentError::NotFound(NotFoundError {
+                message: "/users/0 not found".to_string(),
+                status: 404,
+                path: "/users/0".to_string(),
+            }))
+        );
+    }
+
+    #[test]
+    fn empty_list_under_an_existing_parent_is_empty() {
+        let albums = nested_or_missing(Vec::<AlbumSerde>::new(), || Ok(()));
+        assert_eq!(albums, Ok(vec![]));
+    }
+
+    #[test]
+    fn non_empty_list_skips_the_parent_check() {
+        let album = AlbumSerde {
+            id: 1,
+            user_id: 1,
+            title: "quidem molestiae enim".to_string(),
+        };
+        let albums = nested_or_missing(vec![album.clone()], || {
+            panic!("parent checked for a non-empty list")
+        });
+        assert_eq!(albums, Ok(vec![album]));
+    }
+
+    #[test]
+    fn created_write_reports_its_location() {
+        assert_eq!(
+            write_outcome(201, Some("/posts/101".to_string())),
+            Some(WriteResult {
+                status: 201,
+                location: Some("/posts/101".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn ok_write_without_location_leaves_it_empty() {
+        assert_eq!(
+            write_outcome(200, None),
+            Some(WriteResult {
+                status: 200,
+                location: None,
+            })
+        );
+    }
+
+    #[test]
+    fn writes_answered_without_a_record_have_no_outcome() {
+        for status in [204, 400, 404, 500] {
+            assert_eq!(write_outcome(status, None), None, "{status}");
+        }
+    }
+
+    #[test]
+    fn bare_hosts_and_ports_are_valid_authorities() {
+        for authority in [
+            "localhost:3000",
+            "jsonplaceholder.typicode.com",
+            "[::1]:8080",
+        ] {
+            assert!(is_valid_authority(authority), "{authority:?} rejected");
+        }
+    }
+}
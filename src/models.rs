@@ -0,0 +1,633 @@
+//! Serde mirrors of the WIT records, as jsonplaceholder spells them on the
+//! wire, and the conversions between the two.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::exports::jsonplaceholder::api::jsonplaceholder_api::{
+    Address, AddressPatch, Album, Comment, Company, CompanyPatch, Geo, Photo, Post, Todo, User,
+    UserPatch,
+};
+
+/// jsonplaceholder sends coordinates as strings such as `"-37.3159"`; they
+/// are kept as sent and only parsed when converting to a `Geo`
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub(crate) struct GeoSerde {
+    #[serde(deserialize_with = "de_coordinate")]
+    pub(crate) lat: String,
+    #[serde(deserialize_with = "de_coordinate")]
+    pub(crate) lng: String,
+}
+
+/// Read a coordinate sent either as a string or as a bare number
+fn de_coordinate<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Coordinate {
+        Number(f64),
+        Text(String),
+    }
+
+    Ok(match Coordinate::deserialize(deserializer)? {
+        Coordinate::Number(n) => n.to_string(),
+        Coordinate::Text(t) => t,
+    })
+}
+
+/// Parse the coordinate `value` named `name`; it must be a finite number
+fn parse_coordinate(name: &str, value: &str) -> Result<f64, String> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| format!("{name} {value:?} is not a number"))
+}
+
+impl TryFrom<GeoSerde> for Geo {
+    type Error = String;
+
+    fn try_from(g: GeoSerde) -> Result<Self, Self::Error> {
+        Ok(Geo {
+            lat: parse_coordinate("latitude", &g.lat)?,
+            lng: parse_coordinate("longitude", &g.lng)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub(crate) struct AddressSerde {
+    pub(crate) street: String,
+    pub(crate) suite: String,
+    pub(crate) city: String,
+    pub(crate) zipcode: String,
+    pub(crate) geo: GeoSerde,
+}
+
+impl From<Geo> for GeoSerde {
+    fn from(g: Geo) -> Self {
+        GeoSerde {
+            lat: g.lat.to_string(),
+            lng: g.lng.to_string(),
+        }
+    }
+}
+
+impl TryFrom<AddressSerde> for Address {
+    type Error = String;
+
+    fn try_from(a: AddressSerde) -> Result<Self, Self::Error> {
+        Ok(Address {
+            street: a.street,
+            suite: a.suite,
+            city: a.city,
+            zipcode: a.zipcode,
+            geo: a.geo.try_into()?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub(crate) struct CompanySerde {
+    pub(crate) name: String,
+    #[serde(rename = "catchPhrase")]
+    pub(crate) catch_phrase: String,
+    pub(crate) bs: String,
+}
+
+impl From<Address> for AddressSerde {
+    fn from(a: Address) -> Self {
+        AddressSerde {
+            street: a.street,
+            suite: a.suite,
+            city: a.city,
+            zipcode: a.zipcode,
+            geo: a.geo.into(),
+        }
+    }
+}
+
+impl From<CompanySerde> for Company {
+    fn from(c: CompanySerde) -> Self {
+        Company {
+            name: c.name,
+            catch_phrase: c.catch_phrase,
+            bs: c.bs,
+        }
+    }
+}
+
+impl From<Company> for CompanySerde {
+    fn from(c: Company) -> Self {
+        CompanySerde {
+            name: c.name,
+            catch_phrase: c.catch_phrase,
+            bs: c.bs,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub(crate) struct PostSerde {
+    pub(crate) id: u64,
+    #[serde(rename = "userId")]
+    pub(crate) user_id: u64,
+    pub(crate) title: String,
+    pub(crate) body: String,
+}
+
+impl From<PostSerde> for Post {
+    fn from(p: PostSerde) -> Self {
+        Post {
+            id: p.id,
+            user_id: p.user_id,
+            title: p.title,
+            body: p.body,
+        }
+    }
+}
+
+/// Body of a post create
+#[derive(Serialize)]
+pub(crate) struct CreatePostSerde {
+    #[serde(rename = "userId")]
+    pub(crate) user_id: u64,
+    pub(crate) title: String,
+    pub(crate) body: String,
+}
+
+/// Assembles the payload of a new post, e.g.
+/// `PostBuilder::default().title("t").body("b").user_id(1).build()`
+#[derive(Default)]
+pub(crate) struct PostBuilder {
+    title: Option<String>,
+    body: Option<String>,
+    user_id: Option<u64>,
+}
+
+impl PostBuilder {
+    pub(crate) fn title(mut self, t: impl Into<String>) -> Self {
+        self.title = Some(t.into());
+        self
+    }
+
+    pub(crate) fn body(mut self, b: impl Into<String>) -> Self {
+        self.body = Some(b.into());
+        self
+    }
+
+    pub(crate) fn user_id(mut self, id: u64) -> Self {
+        self.user_id = Some(id);
+        self
+    }
+
+    /// The post without an id; fails naming the first field not set
+    pub(crate) fn build(self) -> Result<CreatePostSerde, String> {
+        Ok(CreatePostSerde {
+            user_id: self.user_id.ok_or("Post user id is required")?,
+            title: self.title.ok_or("Post title is required")?,
+            body: self.body.ok_or("Post body is required")?,
+        })
+    }
+}
+
+/// Partial post update; only the fields that are set get sent
+#[derive(Serialize)]
+pub(crate) struct PatchPostSerde {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) body: Option<String>,
+    #[serde(rename = "userId", skip_serializing_if = "Option::is_none")]
+    pub(crate) user_id: Option<u64>,
+}
+
+/// Post listed with `_embed=comments`
+#[derive(Deserialize)]
+pub(crate) struct EmbeddedPostSerde {
+    #[serde(flatten)]
+    pub(crate) post: PostSerde,
+    pub(crate) comments: Option<Vec<CommentSerde>>,
+}
+
+/// Post fetched with `_expand=user`
+#[derive(Deserialize)]
+pub(crate) struct ExpandedPostSerde {
+    #[serde(flatten)]
+    pub(crate) post: PostSerde,
+    pub(crate) user: Option<UserSerde>,
+}
+
+/// Comment fetched with `_expand=post`
+#[derive(Deserialize)]
+pub(crate) struct ExpandedCommentSerde {
+    #[serde(flatten)]
+    pub(crate) comment: CommentSerde,
+    pub(crate) post: Option<PostSerde>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub(crate) struct UserSerde {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    pub(crate) username: String,
+    pub(crate) email: String,
+    pub(crate) phone: String,
+    pub(crate) website: String,
+    pub(crate) company: CompanySerde,
+    pub(crate) address: AddressSerde,
+}
+
+/// Body of a user create
+#[derive(Serialize)]
+pub(crate) struct CreateUserSerde {
+    pub(crate) name: String,
+    pub(crate) username: String,
+    pub(crate) email: String,
+    pub(crate) phone: String,
+    pub(crate) website: String,
+    pub(crate) company: CompanySerde,
+    pub(crate) address: AddressSerde,
+}
+
+/// Fails when the address carries coordinates that are not numbers
+impl TryFrom<UserSerde> for User {
+    type Error = String;
+
+    fn try_from(u: UserSerde) -> Result<Self, Self::Error> {
+        Ok(User {
+            username: u.username,
+            id: u.id,
+            name: u.name,
+            email: u.email,
+            phone: u.phone,
+            website: u.website,
+            company: u.company.into(),
+            address: u.address.try_into()?,
+        })
+    }
+}
+
+/// Partial user update; only the fields that are set get sent, down to
+/// single fields of the nested company and address
+#[derive(Serialize)]
+pub(crate) struct PatchUserSerde {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) phone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) website: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) company: Option<PatchCompanySerde>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) address: Option<PatchAddressSerde>,
+}
+
+impl From<UserPatch> for PatchUserSerde {
+    fn from(u: UserPatch) -> Self {
+        PatchUserSerde {
+            name: u.name,
+            username: u.username,
+            email: u.email,
+            phone: u.phone,
+            website: u.website,
+            // A nested patch setting nothing would still send `{}`
+            company: u
+                .company
+                .map(PatchCompanySerde::from)
+                .filter(|c| !c.is_empty()),
+            address: u
+                .address
+                .map(PatchAddressSerde::from)
+                .filter(|a| !a.is_empty()),
+        }
+    }
+}
+
+impl PatchUserSerde {
+    /// Whether the patch sets no field at all
+    pub(crate) fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.username.is_none()
+            && self.email.is_none()
+            && self.phone.is_none()
+            && self.website.is_none()
+            && self.company.is_none()
+            && self.address.is_none()
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct PatchCompanySerde {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) name: Option<String>,
+    #[serde(rename = "catchPhrase", skip_serializing_if = "Option::is_none")]
+    pub(crate) catch_phrase: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) bs: Option<String>,
+}
+
+impl PatchCompanySerde {
+    fn is_empty(&self) -> bool {
+        self.name.is_none() && self.catch_phrase.is_none() && self.bs.is_none()
+    }
+}
+
+impl From<CompanyPatch> for PatchCompanySerde {
+    fn from(c: CompanyPatch) -> Self {
+        PatchCompanySerde {
+            name: c.name,
+            catch_phrase: c.catch_phrase,
+            bs: c.bs,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct PatchAddressSerde {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) street: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) suite: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) city: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) zipcode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) geo: Option<PatchGeoSerde>,
+}
+
+impl From<AddressPatch> for PatchAddressSerde {
+    fn from(a: AddressPatch) -> Self {
+        PatchAddressSerde {
+            street: a.street,
+            suite: a.suite,
+            city: a.city,
+            zipcode: a.zipcode,
+            // Coordinates are stored as strings, like a full `GeoSerde`
+            geo: a
+                .geo
+                .map(|g| PatchGeoSerde {
+                    lat: g.lat.map(|v| v.to_string()),
+                    lng: g.lng.map(|v| v.to_string()),
+                })
+                .filter(|g| g.lat.is_some() || g.lng.is_some()),
+        }
+    }
+}
+
+impl PatchAddressSerde {
+    fn is_empty(&self) -> bool {
+        self.street.is_none()
+            && self.suite.is_none()
+            && self.city.is_none()
+            && self.zipcode.is_none()
+            && self.geo.is_none()
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct PatchGeoSerde {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) lat: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) lng: Option<String>,
+}
+
+/// User listed with `_embed=posts`; a user without posts may come without
+/// the field
+#[derive(Deserialize)]
+pub(crate) struct EmbeddedUserSerde {
+    #[serde(flatten)]
+    pub(crate) user: UserSerde,
+    #[serde(default)]
+    pub(crate) posts: Vec<PostSerde>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub(crate) struct CommentSerde {
+    pub(crate) id: u64,
+    #[serde(rename = "postId")]
+    pub(crate) post_id: u64,
+    pub(crate) name: String,
+    pub(crate) email: String,
+    pub(crate) body: String,
+}
+
+/// Body of a comment create
+#[derive(Serialize)]
+pub(crate) struct CreateCommentSerde {
+    #[serde(rename = "postId")]
+    pub(crate) post_id: u64,
+    pub(crate) name: String,
+    pub(crate) email: String,
+    pub(crate) body: String,
+}
+
+/// Partial comment update; only the fields that are set get sent
+#[derive(Serialize)]
+pub(crate) struct PatchCommentSerde {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) body: Option<String>,
+}
+
+impl From<CommentSerde> for Comment {
+    fn from(c: CommentSerde) -> Self {
+        Comment {
+            id: c.id,
+            post_id: c.post_id,
+            name: c.name,
+            email: c.email,
+            body: c.body,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub(crate) struct AlbumSerde {
+    pub(crate) id: u64,
+    #[serde(rename = "userId")]
+    pub(crate) user_id: u64,
+    pub(crate) title: String,
+}
+
+/// Album listed with `_embed=photos`
+#[derive(Deserialize)]
+pub(crate) struct EmbeddedAlbumSerde {
+    #[serde(flatten)]
+    pub(crate) album: AlbumSerde,
+    #[serde(default)]
+    pub(crate) photos: Vec<PhotoSerde>,
+}
+
+/// Body of an album create
+#[derive(Serialize)]
+pub(crate) struct CreateAlbumSerde {
+    pub(crate) title: String,
+    #[serde(rename = "userId")]
+    pub(crate) user_id: u64,
+}
+
+/// Album rename; only the title is sent
+#[derive(Serialize)]
+pub(crate) struct PatchAlbumSerde {
+    pub(crate) title: String,
+}
+
+impl From<AlbumSerde> for Album {
+    fn from(a: AlbumSerde) -> Self {
+        Album {
+            id: a.id,
+            user_id: a.user_id,
+            title: a.title,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub(crate) struct PhotoSerde {
+    pub(crate) id: u64,
+    #[serde(rename = "albumId")]
+    pub(crate) album_id: u64,
+    pub(crate) title: String,
+    pub(crate) url: String,
+    #[serde(rename = "thumbnailUrl")]
+    pub(crate) thumbnail_url: String,
+}
+
+/// Body of a photo create
+#[derive(Serialize)]
+pub(crate) struct CreatePhotoSerde {
+    #[serde(rename = "albumId")]
+    pub(crate) album_id: u64,
+    pub(crate) title: String,
+    pub(crate) url: String,
+    #[serde(rename = "thumbnailUrl")]
+    pub(crate) thumbnail_url: String,
+}
+
+/// Partial photo update; only the fields that are set get sent
+#[derive(Serialize)]
+pub(crate) struct PatchPhotoSerde {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) url: Option<String>,
+    #[serde(rename = "thumbnailUrl", skip_serializing_if = "Option::is_none")]
+    pub(crate) thumbnail_url: Option<String>,
+}
+
+impl From<PhotoSerde> for Photo {
+    fn from(p: PhotoSerde) -> Self {
+        Photo {
+            id: p.id,
+            album_id: p.album_id,
+            thumbnail_url: p.thumbnail_url,
+            title: p.title,
+            url: p.url,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub(crate) struct TodoSerde {
+    pub(crate) id: u64,
+    #[serde(rename = "userId")]
+    pub(crate) user_id: u64,
+    pub(crate) title: String,
+    pub(crate) completed: bool,
+}
+
+/// Body of a todo create
+#[derive(Serialize)]
+pub(crate) struct CreateTodoSerde {
+    pub(crate) title: String,
+    #[serde(rename = "userId")]
+    pub(crate) user_id: u64,
+    pub(crate) completed: bool,
+}
+
+/// Completion toggle; deliberately carries no other todo field
+#[derive(Serialize)]
+pub(crate) struct PatchTodoSerde {
+    pub(crate) completed: bool,
+}
+
+impl From<TodoSerde> for Todo {
+    fn from(t: TodoSerde) -> Self {
+        Todo {
+            id: t.id,
+            user_id: t.user_id,
+            title: t.title,
+            completed: t.completed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn post_fixture_converts_to_an_equal_post() {
+        let fixture = r#"{"userId": 1, "id": 1, "title": "sunt aut", "body": "quia et"}"#;
+        let serde: PostSerde = serde_json::from_str(fixture).unwrap();
+        assert_eq!(
+            serde,
+            PostSerde {
+                id: 1,
+                user_id: 1,
+                title: "sunt aut".to_string(),
+                body: "quia et".to_string(),
+            }
+        );
+
+        assert_eq!(
+            Post::from(serde),
+            Post {
+                id: 1,
+                user_id: 1,
+                title: "sunt aut".to_string(),
+                body: "quia et".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn geo_round_trips_through_its_string_form() {
+        let serde: GeoSerde =
+            serde_json::from_str(r#"{"lat": "-37.3159", "lng": 81.1496}"#).unwrap();
+        let geo = Geo::try_from(serde.clone()).unwrap();
+        assert_eq!(
+            geo,
+            Geo {
+                lat: -37.3159,
+                lng: 81.1496,
+            }
+        );
+        assert_eq!(GeoSerde::from(geo), serde);
+    }
+
+    #[test]
+    fn geo_that_is_not_a_number_fails_to_convert() {
+        let serde: GeoSerde = serde_json::from_str(r#"{"lat": "north", "lng": "0"}"#).unwrap();
+        assert!(Geo::try_from(serde).is_err());
+    }
+
+    #[test]
+    fn todo_toggle_sends_only_completed() {
+        assert_eq!(
+            serde_json::to_string(&PatchTodoSerde { completed: true }).unwrap(),
+            r#"{"completed":true}"#
+        );
+    }
+}
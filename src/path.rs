@@ -0,0 +1,52 @@
+//! Request paths such as `/posts/1/comments?_limit=5`.
+
+use crate::query::QueryBuilder;
+
+/// Builds a request path segment by segment, so resource names are spelled
+/// once; the query is left to a `QueryBuilder`
+pub(crate) struct PathBuilder {
+    path: String,
+    query: QueryBuilder,
+}
+
+impl PathBuilder {
+    /// Start at the top-level collection `name`, e.g. `/posts`
+    pub(crate) fn resource(name: &str) -> Self {
+        PathBuilder {
+            path: format!("/{name}"),
+            query: QueryBuilder::new(),
+        }
+    }
+
+    /// Select one record of the collection, e.g. `/posts/1`
+    pub(crate) fn id(mut self, id: u64) -> Self {
+        self.path.push_str(&format!("/{id}"));
+        self
+    }
+
+    /// Descend into a collection nested under a record, e.g. `/posts/1/comments`
+    pub(crate) fn nested(mut self, name: &str) -> Self {
+        self.path.push('/');
+        self.path.push_str(name);
+        self
+    }
+
+    /// Add `key=value` to the query
+    pub(crate) fn query(mut self, key: &str, value: &str) -> Self {
+        self.query = self.query.push(key, value);
+        self
+    }
+
+    /// Add each of `pairs` whose value is set to the query
+    pub(crate) fn pairs(mut self, pairs: &[(&str, Option<String>)]) -> Self {
+        for (key, value) in pairs {
+            self.query = self.query.push_opt(key, value.as_ref());
+        }
+        self
+    }
+
+    /// The path, followed by the query when any pair was added
+    pub(crate) fn build(self) -> String {
+        format!("{}{}", self.path, self.query.build())
+    }
+}